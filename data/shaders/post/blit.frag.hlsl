@@ -0,0 +1,20 @@
+#pragma shader_stage(fragment)
+
+Texture2D map : register(t0);
+SamplerState sam : register(s1);
+
+struct Input {
+	float4 fragCoord : SV_POSITION;
+	float2 uv : TEXCOORD0;
+};
+
+struct Output {
+	float4 color : SV_TARGET0;
+};
+
+// copies a target of the same size to the frame
+Output main(Input input) {
+	Output o;
+	o.color = map.SampleLevel(sam, input.uv, 0);
+	return o;
+}
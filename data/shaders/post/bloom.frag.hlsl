@@ -0,0 +1,26 @@
+#pragma shader_stage(fragment)
+
+#include "post/common.hlsl"
+
+// single pass approximation, bright texels around the fragment are blurred and added
+Output main(Input input) {
+	Output o;
+	float threshold = params.x;
+	float intensity = params.y;
+	float radius = params.z;
+
+	float4 color = map.Sample(sam, input.uv);
+	float3 bloom = float3(0.0, 0.0, 0.0);
+	float total = 0.0;
+	for (int x = -2; x <= 2; x++) {
+		for (int y = -2; y <= 2; y++) {
+			float weight = exp(-float(x * x + y * y) / 4.0);
+			float2 offset = float2(x, y) * radius * resolution.zw;
+			float3 texel = map.SampleLevel(sam, input.uv + offset, 0).rgb;
+			bloom += max(texel - threshold, 0.0) * weight;
+			total += weight;
+		}
+	}
+	o.color = float4(color.rgb + bloom / total * intensity, color.a);
+	return o;
+}
@@ -0,0 +1,23 @@
+#pragma shader_stage(fragment)
+
+#include "post/common.hlsl"
+
+// lut is 256x16, blue selects one of 16 horizontal slices
+float3 grade(float3 color) {
+	float3 c = saturate(color) * 15.0;
+	float slice0 = floor(c.b);
+	float slice1 = min(slice0 + 1.0, 15.0);
+	float2 uv0 = float2((slice0 * 16.0 + c.r + 0.5) / 256.0, (c.g + 0.5) / 16.0);
+	float2 uv1 = float2((slice1 * 16.0 + c.r + 0.5) / 256.0, (c.g + 0.5) / 16.0);
+	float3 graded0 = lut.SampleLevel(sam, uv0, 0).rgb;
+	float3 graded1 = lut.SampleLevel(sam, uv1, 0).rgb;
+	return lerp(graded0, graded1, c.b - slice0);
+}
+
+Output main(Input input) {
+	Output o;
+	float4 color = map.Sample(sam, input.uv);
+	float strength = params.x;
+	o.color = float4(lerp(color.rgb, grade(color.rgb), strength), color.a);
+	return o;
+}
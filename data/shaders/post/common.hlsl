@@ -0,0 +1,17 @@
+cbuffer Effect : register(b0) {
+	float4 params;
+	float4 resolution;
+}
+
+Texture2D map : register(t1);
+SamplerState sam : register(s2);
+Texture2D lut : register(t3);
+
+struct Input {
+	float4 fragCoord : SV_POSITION;
+	float2 uv : TEXCOORD0;
+};
+
+struct Output {
+	float4 color : SV_TARGET0;
+};
@@ -0,0 +1,25 @@
+#pragma shader_stage(fragment)
+
+#include "post/common.hlsl"
+
+static const float PI = 3.14159265;
+
+Output main(Input input) {
+	Output o;
+	float scanlines = params.x;
+	float intensity = params.y;
+	float curvature = params.z;
+
+	float2 centered = input.uv * 2.0 - 1.0;
+	centered *= 1.0 + curvature * dot(centered.yx, centered.yx) * 0.25;
+	float2 uv = centered * 0.5 + 0.5;
+	if (any(uv < 0.0) || any(uv > 1.0)) {
+		o.color = float4(0.0, 0.0, 0.0, 1.0);
+		return o;
+	}
+
+	float4 color = map.Sample(sam, uv);
+	float scanline = 0.5 + 0.5 * sin(uv.y * scanlines * 2.0 * PI);
+	o.color = float4(color.rgb * (1.0 - intensity * scanline), color.a);
+	return o;
+}
@@ -0,0 +1,15 @@
+#pragma shader_stage(vertex)
+
+struct Output {
+	float4 position : SV_POSITION;
+	float2 uv : TEXCOORD0;
+};
+
+// one triangle covering the whole target
+Output main(uint vertexID : SV_VERTEXID) {
+	Output o;
+	float2 uv = float2((vertexID << 1) & 2, vertexID & 2);
+	o.position = float4(uv * float2(2.0, -2.0) + float2(-1.0, 1.0), 0.0, 1.0);
+	o.uv = uv;
+	return o;
+}
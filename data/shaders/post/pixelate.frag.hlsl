@@ -0,0 +1,12 @@
+#pragma shader_stage(fragment)
+
+#include "post/common.hlsl"
+
+Output main(Input input) {
+	Output o;
+	float size = max(params.x, 1.0);
+	float2 cells = resolution.xy / size;
+	float2 uv = (floor(input.uv * cells) + 0.5) / cells;
+	o.color = map.SampleLevel(sam, uv, 0);
+	return o;
+}
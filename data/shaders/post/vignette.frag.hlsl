@@ -0,0 +1,15 @@
+#pragma shader_stage(fragment)
+
+#include "post/common.hlsl"
+
+Output main(Input input) {
+	Output o;
+	float intensity = params.x;
+	float radius = params.y;
+	float softness = params.z;
+	float4 color = map.Sample(sam, input.uv);
+	float2 offset = (input.uv - 0.5) * float2(resolution.x * resolution.w, 1.0);
+	float vignette = smoothstep(radius, radius - softness, length(offset));
+	o.color = float4(color.rgb * lerp(1.0, vignette, intensity), color.a);
+	return o;
+}
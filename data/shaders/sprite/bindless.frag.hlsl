@@ -0,0 +1,27 @@
+#pragma shader_stage(fragment)
+
+// must match MAX_BINDING_ARRAY_TEXTURES in graphics/pipelines/sprite.rs
+Texture2D maps[16] : register(t1);
+SamplerState sam : register(s2);
+
+#include "sprite/color.hlsl"
+
+struct Input {
+	float4 fragCoord : SV_POSITION;
+	float2 uv : TEXCOORD0;
+	nointerpolation uint layer : TEXCOORD1;
+	nointerpolation float4 color : COLOR0;
+	nointerpolation uint palette : TEXCOORD2;
+	nointerpolation uint flags : TEXCOORD3;
+};
+
+struct Output {
+	float4 color : SV_TARGET0;
+};
+
+Output main(Input input) {
+	Output o;
+	float4 color = maps[NonUniformResourceIndex(input.layer)].Sample(sam, float2(input.uv.x, input.uv.y));
+	o.color = apply_color(color, input.color, input.palette, input.flags);
+	return o;
+}
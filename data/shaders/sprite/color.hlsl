@@ -0,0 +1,21 @@
+Texture2D palette_map : register(t3);
+SamplerState palette_sam : register(s4);
+
+// must match SpriteData::PREMULTIPLY in components.rs
+static const uint PREMULTIPLY = 4;
+
+float4 apply_color(float4 color, float4 tint, uint palette, uint flags) {
+	if (palette > 0) {
+		float width;
+		float height;
+		palette_map.GetDimensions(width, height);
+		float index = dot(color.rgb, float3(0.299, 0.587, 0.114));
+		float row = (float(palette) - 0.5) / height;
+		color.rgb = palette_map.SampleLevel(palette_sam, float2(index, row), 0).rgb;
+	}
+	color *= tint;
+	if (flags & PREMULTIPLY) {
+		color.rgb *= color.a;
+	}
+	return color;
+}
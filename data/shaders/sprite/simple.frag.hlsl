@@ -1,11 +1,17 @@
 #pragma shader_stage(fragment)
 
-Texture2D map : register(t1);
+Texture2DArray map : register(t1);
 SamplerState sam : register(s2);
 
+#include "sprite/color.hlsl"
+
 struct Input {
 	float4 fragCoord : SV_POSITION;
 	float2 uv : TEXCOORD0;
+	nointerpolation uint layer : TEXCOORD1;
+	nointerpolation float4 color : COLOR0;
+	nointerpolation uint palette : TEXCOORD2;
+	nointerpolation uint flags : TEXCOORD3;
 };
 
 struct Output {
@@ -14,9 +20,7 @@ struct Output {
 
 Output main(Input input) {
 	Output o;
-    float width;
-	float height;
-	map.GetDimensions(width, height);
-	o.color = map.Sample(sam, float2(input.uv.x, input.uv.y));
+	float4 color = map.Sample(sam, float3(input.uv.x, input.uv.y, input.layer));
+	o.color = apply_color(color, input.color, input.palette, input.flags);
 	return o;
 }
@@ -13,13 +13,26 @@ struct Input {
 
     float2 texture_position;
     float2 texture_size;
+    float4 tint;
+    float opacity;
+    uint palette;
+    uint texture_layer;
+    uint flags;
+    float2 pivot;
 };
 
 struct Output {
 	float4 position : SV_POSITION;
 	float2 uv : TEXCOORD0;
+	nointerpolation uint layer : TEXCOORD1;
+	nointerpolation float4 color : COLOR0;
+	nointerpolation uint palette : TEXCOORD2;
+	nointerpolation uint flags : TEXCOORD3;
 };
 
+static const uint FLIP_X = 1;
+static const uint FLIP_Y = 2;
+
 float2 rotate_point(float pointX, float pointY, float originX, float originY, float angle) {
     return float2(
         cos(angle) * (pointX-originX) - sin(angle) * (pointY-originY) + originX,
@@ -30,47 +43,38 @@ float2 rotate_point(float pointX, float pointY, float originX, float originY, fl
 Output main(Input input) {
 	Output o;
 
-    float tpx = input.texture_position.x;
-    float tpy = input.texture_position.y;
-    float tsx = input.texture_size.x;
-    float tsy = input.texture_size.y;
-
-    float2 utl = float2(tpx,       tpy      );
-	float2 ubl = float2(tpx,       tpy + tsy);
-	float2 ubr = float2(tpx + tsx, tpy + tsy);
-	float2 utr = float2(tpx + tsx, tpy      );
-
-	float2 points[6] = {
-		utl,
-		ubl,
-		ubr,
-		ubr,
-		utr,
-		utl
+	// quad corners with (0, 0) at the bottom left
+	float2 corners[6] = {
+		float2(0, 1),
+		float2(0, 0),
+		float2(1, 0),
+		float2(1, 0),
+		float2(1, 1),
+		float2(0, 1)
 	};
+	float2 corner = corners[input.vertexID];
+
+	float2 uv = float2(corner.x, 1.0 - corner.y);
+	if (input.flags & FLIP_X) {
+		uv.x = 1.0 - uv.x;
+	}
+	if (input.flags & FLIP_Y) {
+		uv.y = 1.0 - uv.y;
+	}
 
     float2 offset = float2(input.position.x, input.position.y);
-    float sx = input.size.x;
-    float sy = input.size.y;
+    float2 local = (corner - input.pivot) * 2.0 * float2(input.size.x, input.size.y);
     float pz = input.position.z;
-	float angle = input.rotation[0];
-
-	float3 tl = float3(rotate_point(-sx, +sy, 0, 0, angle) + offset, pz);
-	float3 bl = float3(rotate_point(-sx, -sy, 0, 0, angle) + offset, pz);
-	float3 br = float3(rotate_point(+sx, -sy, 0, 0, angle) + offset, pz);
-	float3 tr = float3(rotate_point(+sx, +sy, 0, 0, angle) + offset, pz);
+	float angle = input.rotation.x;
 
-	float3 positions[6] = {
-		tl,
-		bl,
-		br,
-		br,
-		tr,
-		tl,
-	};
+	float3 position = float3(rotate_point(local.x, local.y, 0, 0, angle) + offset, pz);
 
-	o.position = float4(positions[input.vertexID], 1.0) * projection;
-    o.uv = points[input.vertexID];
+	o.position = float4(position, 1.0) * projection;
+    o.uv = input.texture_position + uv * input.texture_size;
+    o.layer = input.texture_layer;
+    o.color = float4(input.tint.rgb, input.tint.a * input.opacity);
+    o.palette = input.palette;
+    o.flags = input.flags;
 
     return o;
 }
@@ -0,0 +1,197 @@
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use serde::{Deserialize, Serialize};
+use shipyard::{UniqueView, UniqueViewMut};
+
+use crate::{
+    config::Config,
+    graphics::{renderer::Renderer, stats::FrameSample},
+    setup::create_headless_universe,
+};
+
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BenchConfig {
+    // sprite counts to sweep
+    pub counts: Vec<u32>,
+    pub warmup: u32,
+    pub frames: u32,
+    pub width:  u32,
+    pub height: u32,
+    pub output: PathBuf,
+}
+impl Default for BenchConfig {
+    fn default() -> Self {
+        Self {
+            counts: vec![1_000, 5_000, 10_000, 25_000, 50_000, 100_000, 200_000],
+            warmup: 10,
+            frames: 100,
+            width:  1024,
+            height: 1024,
+            output: PathBuf::from("./bench"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BenchFrame {
+    pub frame:    u32,
+    pub frame_ms: f64,
+    #[serde(flatten)]
+    pub sample:   FrameSample,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BenchResult {
+    pub entities:        u32,
+    pub mean_frame_ms:   f64,
+    pub mean_prepare_ms: f64,
+    pub max_prepare_ms:  f64,
+    pub mean_gpu_ms:     Option<f64>,
+    pub upload_bytes:    u64,
+    pub draw_calls:      u64,
+    pub frames:          Vec<BenchFrame>,
+}
+impl BenchResult {
+    fn new(entities: u32, frames: Vec<BenchFrame>) -> Self {
+        let count = frames.len().max(1) as f64;
+        let gpu = frames
+            .iter()
+            .filter_map(|frame| frame.sample.gpu_ms)
+            .collect::<Vec<_>>();
+        Self {
+            entities,
+            mean_frame_ms: frames.iter().map(|frame| frame.frame_ms).sum::<f64>() / count,
+            mean_prepare_ms: frames
+                .iter()
+                .map(|frame| frame.sample.prepare_ms)
+                .sum::<f64>()
+                / count,
+            max_prepare_ms: frames
+                .iter()
+                .map(|frame| frame.sample.prepare_ms)
+                .fold(0.0, f64::max),
+            mean_gpu_ms: if gpu.is_empty() {
+                None
+            } else {
+                Some(gpu.iter().sum::<f64>() / gpu.len() as f64)
+            },
+            upload_bytes: frames.last().map_or(0, |frame| frame.sample.upload_bytes),
+            draw_calls: frames.last().map_or(0, |frame| frame.sample.draw_calls),
+            frames,
+        }
+    }
+}
+
+pub fn run_bench_count(config: &Config, entities: u32) -> Result<BenchResult, String> {
+    let bench = &config.bench;
+    let mut config = Config {
+        trace: false,
+        ..config.clone()
+    };
+    config.record.enabled = false;
+    config.scene.entities = entities;
+    let mut universe = create_headless_universe(&config)?;
+    universe.resize(bench.width, bench.height);
+    let timed = universe
+        .world
+        .run(|mut renderer: UniqueViewMut<Renderer>| renderer.enable_gpu_timer());
+    if !timed {
+        tracing::warn!("timestamp queries are not supported, gpu times are not recorded");
+    }
+
+    let mut frames = Vec::with_capacity(bench.frames as usize);
+    for frame in 0..bench.warmup + bench.frames {
+        let start = Instant::now();
+        universe.render();
+        // reading the gpu timer waits for the gpu, so it is excluded from the frame time
+        let frame_ms = start.elapsed().as_secs_f64() * 1000.0;
+        let sample = universe
+            .world
+            .run(|renderer: UniqueView<Renderer>| FrameSample {
+                gpu_ms: renderer
+                    .timer
+                    .as_ref()
+                    .and_then(|timer| timer.read(&renderer.device)),
+                ..renderer.stats.sample()
+            });
+        if frame >= bench.warmup {
+            frames.push(BenchFrame {
+                frame: frame - bench.warmup,
+                frame_ms,
+                sample,
+            });
+        }
+    }
+    Ok(BenchResult::new(entities, frames))
+}
+
+pub fn write_bench_results(path: &Path, results: &[BenchResult]) -> std::io::Result<()> {
+    std::fs::create_dir_all(path)?;
+    std::fs::write(
+        path.join("bench.json"),
+        serde_json::to_string_pretty(results)?,
+    )?;
+
+    let mut csv =
+        String::from("entities,frame,frame_ms,prepare_ms,upload_bytes,draw_calls,gpu_ms\n");
+    for result in results {
+        for frame in result.frames.iter() {
+            writeln!(
+                csv,
+                "{},{},{:.4},{:.4},{},{},{}",
+                result.entities,
+                frame.frame,
+                frame.frame_ms,
+                frame.sample.prepare_ms,
+                frame.sample.upload_bytes,
+                frame.sample.draw_calls,
+                frame
+                    .sample
+                    .gpu_ms
+                    .map_or_else(String::new, |gpu_ms| format!("{:.4}", gpu_ms))
+            )
+            .unwrap();
+        }
+    }
+    std::fs::write(path.join("bench.csv"), csv)
+}
+
+// runs every configured sprite count headlessly and writes the results to the output directory
+pub fn run_bench(config: &Config) -> bool {
+    let mut results = Vec::new();
+    for entities in config.bench.counts.iter() {
+        match run_bench_count(config, *entities) {
+            Ok(result) => {
+                println!(
+                    "{} sprites: frame {:.3}ms, prepare {:.3}ms, gpu {}, upload {} bytes, {} draws",
+                    result.entities,
+                    result.mean_frame_ms,
+                    result.mean_prepare_ms,
+                    result
+                        .mean_gpu_ms
+                        .map_or_else(|| "n/a".to_owned(), |gpu_ms| format!("{:.3}ms", gpu_ms)),
+                    result.upload_bytes,
+                    result.draw_calls
+                );
+                results.push(result);
+            }
+            Err(e) => {
+                tracing::error!("bench with {} sprites failed: {}", entities, e);
+                return false;
+            }
+        }
+    }
+    match write_bench_results(&config.bench.output, &results) {
+        Ok(()) => true,
+        Err(e) => {
+            tracing::error!("failed to write bench results: {}", e);
+            false
+        }
+    }
+}
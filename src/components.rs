@@ -1,25 +1,131 @@
 #![allow(clippy::identity_op)]
 
+use std::time::Instant;
+
 use bytemuck::{Pod, Zeroable};
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use shipyard::EntityId;
 use ultraviolet::{projection::perspective_wgpu_dx, Mat4, Vec2, Vec3, Vec4};
+use wgpu::Color;
+
+use crate::graphics::{
+    blend::BlendMode,
+    graph::{Attachment, Viewport},
+    texture::SamplerConfig,
+};
 
 #[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
 #[repr(C, align(16))]
 pub struct Transform {
     pub position: Vec4,
+    // x is the counter-clockwise rotation around the sprite pivot in radians
     pub rotation: Vec4,
+    // half extent of the sprite quad
     pub size:     Vec4,
 }
+impl Transform {
+    pub fn new(position: Vec3, size: Vec2) -> Self {
+        Self {
+            position: Vec4::new(position.x, position.y, position.z, 1.0),
+            rotation: Vec4::zero(),
+            size:     Vec4::new(size.x, size.y, 0.5, 1.0),
+        }
+    }
+
+    pub fn with_angle(self, angle: f32) -> Self {
+        Self {
+            rotation: Vec4::new(angle, 0.0, 0.0, 0.0),
+            ..self
+        }
+    }
+
+    pub fn with_degrees(self, degrees: f32) -> Self { self.with_angle(degrees.to_radians()) }
+
+    pub fn angle(&self) -> f32 { self.rotation.x }
+
+    pub fn set_angle(&mut self, angle: f32) { self.rotation.x = angle % std::f32::consts::TAU; }
+
+    pub fn rotate(&mut self, angle: f32) { self.set_angle(self.angle() + angle); }
+
+    // places a transform relative to this one, sizes are not inherited
+    pub fn compose(&self, local: &Transform) -> Self {
+        let (sin, cos) = self.angle().sin_cos();
+        let (x, y) = (local.position.x, local.position.y);
+        Self {
+            position: Vec4::new(
+                self.position.x + x * cos - y * sin,
+                self.position.y + x * sin + y * cos,
+                self.position.z + local.position.z,
+                1.0,
+            ),
+            rotation: Vec4::new(
+                (self.angle() + local.angle()) % std::f32::consts::TAU,
+                0.0,
+                0.0,
+                0.0,
+            ),
+            size:     local.size,
+        }
+    }
+
+    // the inverse of compose, the transform relative to the given parent
+    pub fn relative_to(&self, parent: &Transform) -> Self {
+        let (sin, cos) = (-parent.angle()).sin_cos();
+        let (x, y) = (
+            self.position.x - parent.position.x,
+            self.position.y - parent.position.y,
+        );
+        Self {
+            position: Vec4::new(
+                x * cos - y * sin,
+                x * sin + y * cos,
+                self.position.z - parent.position.z,
+                1.0,
+            ),
+            rotation: Vec4::new(
+                (self.angle() - parent.angle()) % std::f32::consts::TAU,
+                0.0,
+                0.0,
+                0.0,
+            ),
+            size:     self.size,
+        }
+    }
+}
+
+// world transform sprites are drawn, collided and picked with, propagated from the Transform
+// of entities without a parent and the LocalTransform of children
+#[derive(Copy, Clone, Debug, Default)]
+pub struct GlobalTransform(pub Transform);
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LocalTransform(pub Transform);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Parent(pub EntityId);
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Children(pub Vec<EntityId>);
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct Camera {
-    pub eye:    Vec3,
-    pub target: Vec3,
-    pub up:     Vec3,
-    pub aspect: f32,
-    pub fovy:   f32,
-    pub znear:  f32,
-    pub zfar:   f32,
+    pub eye:      Vec3,
+    pub target:   Vec3,
+    pub up:       Vec3,
+    // derived from the viewport when rendering
+    pub aspect:   f32,
+    pub fovy:     f32,
+    pub znear:    f32,
+    pub zfar:     f32,
+    pub viewport: Viewport,
+    // clears the whole output whatever the viewport, so it's ignored on every camera after the
+    // first writing the same output, including the frame behind post processing
+    pub clear:    Option<Color>,
+    // cameras are drawn in ascending order
+    pub order:    i32,
+    pub output:   Attachment,
+    // sprites are drawn if they share a layer with the camera
+    pub layers:   RenderLayers,
 }
 impl Camera {
     pub fn new(aspect: f32) -> Self {
@@ -31,9 +137,24 @@ impl Camera {
             fovy: 90.0,
             znear: 0.0,
             zfar: 100.0,
+            viewport: Viewport::FULL,
+            clear: Some(Color::BLACK),
+            order: 0,
+            output: Attachment::Scene,
+            layers: RenderLayers::DEFAULT,
         }
     }
 
+    pub fn with_viewport(self, viewport: Viewport) -> Self { Self { viewport, ..self } }
+
+    pub fn with_clear(self, clear: Option<Color>) -> Self { Self { clear, ..self } }
+
+    pub fn with_order(self, order: i32) -> Self { Self { order, ..self } }
+
+    pub fn with_output(self, output: Attachment) -> Self { Self { output, ..self } }
+
+    pub fn with_layers(self, layers: RenderLayers) -> Self { Self { layers, ..self } }
+
     pub fn projection(&self) -> Mat4 {
         perspective_wgpu_dx(self.fovy.to_radians(), self.aspect, self.znear, self.zfar)
     }
@@ -52,17 +173,246 @@ pub struct CameraData {
     pub projection: Mat4,
 }
 
+// bitmask of render layers, sprites without the component are on layer 0
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RenderLayers(pub u32);
+impl RenderLayers {
+    pub const ALL: Self = RenderLayers(!0);
+    pub const DEFAULT: Self = RenderLayers(1);
+    pub const NONE: Self = RenderLayers(0);
+
+    pub fn layer(layer: u32) -> Self { RenderLayers(1 << layer) }
+
+    pub fn with(self, layer: u32) -> Self { RenderLayers(self.0 | 1 << layer) }
+
+    pub fn without(self, layer: u32) -> Self { RenderLayers(self.0 & !(1 << layer)) }
+
+    pub fn intersects(self, other: Self) -> bool { self.0 & other.0 != 0 }
+}
+impl Default for RenderLayers {
+    fn default() -> Self { Self::DEFAULT }
+}
+impl std::ops::BitOr for RenderLayers {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self { RenderLayers(self.0 | other.0) }
+}
+
 #[derive(Copy, Clone, Debug, Default)]
 pub struct Sprite {
     pub data:    SpriteData,
     pub texture: u64,
+    // overrides the sampler configured for the texture
+    pub sampler: Option<SamplerConfig>,
+    pub blend:   BlendMode,
 }
 impl Sprite {
     pub fn data(&self) -> &SpriteData { &self.data }
+
+    pub fn texture_key(&self) -> (u64, Option<SamplerConfig>) { (self.texture, self.sampler) }
 }
-#[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
 #[repr(C, align(16))]
 pub struct SpriteData {
     pub texture_position: Vec2,
     pub texture_size:     Vec2,
+    pub tint:             Vec4,
+    pub opacity:          f32,
+    // row in the palette lookup texture plus one, 0 disables palette remapping
+    pub palette:          u32,
+    pub texture_layer:    u32,
+    pub flags:            u32,
+    // normalized origin of the quad for positioning and rotation, (0, 0) is the bottom left
+    pub pivot:            Vec2,
+    pub _padding:         Vec2,
+}
+impl SpriteData {
+    pub const FLIP_X: u32 = 1 << 0;
+    pub const FLIP_Y: u32 = 1 << 1;
+    pub const PIVOT_BOTTOM: Vec2 = Vec2 { x: 0.5, y: 0.0 };
+    pub const PIVOT_CENTER: Vec2 = Vec2 { x: 0.5, y: 0.5 };
+    // set by the sprite pipeline for blend modes that need premultiplied output
+    pub const PREMULTIPLY: u32 = 1 << 2;
+
+    pub fn new(texture_position: Vec2, texture_size: Vec2) -> Self {
+        Self {
+            texture_position,
+            texture_size,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_tint(self, tint: Vec4) -> Self { Self { tint, ..self } }
+
+    pub fn with_opacity(self, opacity: f32) -> Self { Self { opacity, ..self } }
+
+    pub fn with_palette(self, row: u32) -> Self {
+        Self {
+            palette: row + 1,
+            ..self
+        }
+    }
+
+    pub fn with_pivot(self, pivot: Vec2) -> Self { Self { pivot, ..self } }
+
+    pub fn with_flip(self, flip_x: bool, flip_y: bool) -> Self {
+        let mut flags = self.flags & !(Self::FLIP_X | Self::FLIP_Y);
+        if flip_x {
+            flags |= Self::FLIP_X;
+        }
+        if flip_y {
+            flags |= Self::FLIP_Y;
+        }
+        Self { flags, ..self }
+    }
+
+    pub fn flip_x(&self) -> bool { self.flags & Self::FLIP_X != 0 }
+
+    pub fn flip_y(&self) -> bool { self.flags & Self::FLIP_Y != 0 }
+}
+impl Default for SpriteData {
+    fn default() -> Self {
+        Self {
+            texture_position: Vec2::zero(),
+            texture_size:     Vec2::one(),
+            tint:             Vec4::one(),
+            opacity:          1.0,
+            palette:          0,
+            texture_layer:    0,
+            flags:            0,
+            pivot:            Self::PIVOT_CENTER,
+            _padding:         Vec2::zero(),
+        }
+    }
+}
+
+// world units per second
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Velocity(pub Vec2);
+
+// counter-clockwise radians per second
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct AngularVelocity(pub f32);
+
+// world units per second squared
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Acceleration(pub Vec2);
+
+// exponential decay rates per second, independent of the timestep
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Damping {
+    pub linear:  f32,
+    pub angular: f32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Shape {
+    // ignores the transform rotation
+    Aabb,
+    // the radius is the larger half extent
+    Circle,
+    Obb,
+}
+
+// centered on the transform position and sized from its half extent
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Collider {
+    pub shape:   Shape,
+    // two colliders are tested if each one's layer is in the other's mask
+    pub layer:   u32,
+    pub mask:    u32,
+    // triggers report collision events for consumers to treat as overlaps
+    pub trigger: bool,
+}
+impl Collider {
+    pub fn new(shape: Shape) -> Self {
+        Self {
+            shape,
+            layer: 1,
+            mask: !0,
+            trigger: false,
+        }
+    }
+
+    pub fn with_layer(self, layer: u32) -> Self { Self { layer, ..self } }
+
+    pub fn with_mask(self, mask: u32) -> Self { Self { mask, ..self } }
+
+    pub fn trigger(self) -> Self {
+        Self {
+            trigger: true,
+            ..self
+        }
+    }
+
+    pub fn interacts(&self, other: &Collider) -> bool {
+        self.layer & other.mask != 0 && other.layer & self.mask != 0
+    }
+}
+
+// simulation clock, advanced once per rendered frame
+pub struct Time {
+    // seconds since the last frame
+    pub delta:    f32,
+    pub elapsed:  f64,
+    pub frame:    u64,
+    // replaces the measured frame time when set, used while recording
+    pub timestep: Option<f32>,
+    // unset until the first frame so setup time isn't counted
+    last:         Option<Instant>,
+}
+impl Time {
+    // measured frame times are clamped so stalls don't make the simulation jump
+    pub const MAX_DELTA: f32 = 0.1;
+
+    pub fn new() -> Self {
+        Self {
+            delta:    0.0,
+            elapsed:  0.0,
+            frame:    0,
+            timestep: None,
+            last:     None,
+        }
+    }
+
+    pub fn advance(&mut self) {
+        let now = Instant::now();
+        let measured = self.last.map_or(0.0, |last| {
+            now.duration_since(last).as_secs_f32().min(Self::MAX_DELTA)
+        });
+        self.delta = self.timestep.unwrap_or(measured);
+        self.last = Some(now);
+        self.elapsed += self.delta as f64;
+        self.frame += 1;
+    }
+}
+impl Default for Time {
+    fn default() -> Self { Self::new() }
+}
+
+// shared random source for scene construction and systems, reproducible from its seed
+pub struct Rng {
+    seed: u64,
+    rng:  StdRng,
+}
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 { self.seed }
+}
+impl RngCore for Rng {
+    fn next_u32(&mut self) -> u32 { self.rng.next_u32() }
+
+    fn next_u64(&mut self) -> u64 { self.rng.next_u64() }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) { self.rng.fill_bytes(dest) }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
 }
@@ -0,0 +1,331 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use wgpu::{BackendBit, PowerPreference};
+
+use crate::{
+    bench::BenchConfig,
+    graphics::{capture::RecordConfig, renderer::PresentMode},
+    resources::get_path,
+    universe::Scene,
+};
+
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Backend {
+    Vulkan,
+    Metal,
+    Dx12,
+    Dx11,
+    Gl,
+    Primary,
+    All,
+}
+impl From<Backend> for BackendBit {
+    fn from(backend: Backend) -> Self {
+        match backend {
+            Backend::Vulkan => BackendBit::VULKAN,
+            Backend::Metal => BackendBit::METAL,
+            Backend::Dx12 => BackendBit::DX12,
+            Backend::Dx11 => BackendBit::DX11,
+            Backend::Gl => BackendBit::GL,
+            Backend::Primary => BackendBit::PRIMARY,
+            Backend::All => BackendBit::all(),
+        }
+    }
+}
+
+impl std::str::FromStr for Backend {
+    type Err = String;
+
+    fn from_str(backend: &str) -> Result<Self, Self::Err> {
+        match backend {
+            "vulkan" => Ok(Backend::Vulkan),
+            "metal" => Ok(Backend::Metal),
+            "dx12" => Ok(Backend::Dx12),
+            "dx11" => Ok(Backend::Dx11),
+            "gl" => Ok(Backend::Gl),
+            "primary" => Ok(Backend::Primary),
+            "all" => Ok(Backend::All),
+            _ => Err(format!("unknown backend {}", backend)),
+        }
+    }
+}
+
+// an index into the adapter list or a case insensitive part of the adapter name
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AdapterSelector {
+    Index(usize),
+    Name(String),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Power {
+    Low,
+    High,
+}
+impl From<Power> for PowerPreference {
+    fn from(power: Power) -> Self {
+        match power {
+            Power::Low => PowerPreference::LowPower,
+            Power::High => PowerPreference::HighPerformance,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub width:      u32,
+    pub height:     u32,
+    pub min_size:   Option<(u32, u32)>,
+    pub fullscreen: bool,
+}
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            width:      1024,
+            height:     1024,
+            min_size:   Some((1024, 1024)),
+            fullscreen: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // tried in order until one provides an adapter
+    pub backends:     Vec<Backend>,
+    pub adapter:      Option<AdapterSelector>,
+    pub power:        Power,
+    pub trace:        bool,
+    pub trace_path:   PathBuf,
+    pub window:       WindowConfig,
+    pub present_mode: PresentMode,
+    pub sample_count: u32,
+    pub srgb:         bool,
+    // screenshots are saved here when F12 is pressed
+    pub screenshots:  PathBuf,
+    pub record:       RecordConfig,
+    // seconds simulated per frame, the measured frame time when unset
+    pub timestep:     Option<f32>,
+    pub scene:        Scene,
+    pub bench:        BenchConfig,
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            backends:     vec![Backend::Vulkan, Backend::Gl, Backend::All],
+            adapter:      None,
+            power:        Power::High,
+            trace:        true,
+            trace_path:   PathBuf::from("./trace"),
+            window:       WindowConfig::default(),
+            present_mode: PresentMode::Mailbox,
+            sample_count: 4,
+            srgb:         true,
+            screenshots:  PathBuf::from("./screenshots"),
+            record:       RecordConfig::default(),
+            timestep:     None,
+            scene:        Scene::default(),
+            bench:        BenchConfig::default(),
+        }
+    }
+}
+impl Config {
+    pub fn backend_bits(&self) -> BackendBit {
+        self.backends
+            .iter()
+            .fold(BackendBit::empty(), |bits, backend| {
+                bits | (*backend).into()
+            })
+    }
+
+    pub fn trace_path(&self) -> Option<&Path> {
+        if self.trace {
+            Some(&self.trace_path)
+        } else {
+            None
+        }
+    }
+}
+
+pub enum Mode {
+    Run,
+    Help,
+    ListAdapters,
+    Bench,
+    Scenarios(Vec<String>),
+}
+
+pub const USAGE: &str = "options:
+    --help                      print this message
+    --config <path>             config file, defaults to data/config.ron
+    --backend <backends>        comma separated fallback list of vulkan, metal, dx12, dx11,
+                                gl, primary or all
+    --adapter <index|name>      pick an adapter by its index or a part of its name
+    --list-adapters             list the available adapters and exit
+    --power <low|high>          adapter power preference
+    --trace [path]              write a wgpu trace, optionally to the given directory
+    --no-trace                  disable the wgpu trace
+    --width <width>             window width
+    --height <height>           window height
+    --fullscreen                borderless fullscreen window
+    --windowed                  regular window
+    --vsync                     use the vsync present mode
+    --present-mode <mode>       vsync, mailbox or immediate
+    --samples <count>           msaa sample count, 1, 2, 4 or 8 if the adapter supports it
+    --srgb, --no-srgb           srgb color textures and blending
+    --screenshots <path>        directory for screenshots taken with F12
+    --record [path]             record frames from launch, F11 toggles recording
+    --record-every <n>          capture every nth frame while recording
+    --record-timestep <seconds> simulated time per frame while recording
+    --record-frames <count>     stop recording after this many captured frames
+    --scene <path>              load a saved scene
+    --timestep <seconds>        fixed simulation step instead of the measured frame time
+    --seed <seed>               scene random seed, random and logged when unset
+    --entities <count>          number of sprites in the scene
+    --texture <name>            scene texture in data/images, repeatable
+    --scenario [paths...]       run scenarios headlessly, defaults to data/scenarios
+    --bench [counts]            run the benchmark, optionally with comma separated sprite counts
+    --bench-frames <frames>     measured frames per sprite count
+    --bench-output <path>       directory for bench.csv and bench.json";
+
+pub fn get_config<P: AsRef<Path>>(path: P) -> Result<Config, String> {
+    let path = path.as_ref();
+    let config = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read config {:?}: {}", path, e))?;
+    ron::from_str(&config).map_err(|e| format!("invalid config {:?}: {}", path, e))
+}
+
+pub fn get_scene<P: AsRef<Path>>(path: P) -> Result<Scene, String> {
+    let path = path.as_ref();
+    let scene = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read scene {:?}: {}", path, e))?;
+    ron::from_str(&scene).map_err(|e| format!("invalid scene {:?}: {}", path, e))
+}
+
+fn parse<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("missing value for {}", flag))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {}: {}", flag, value))
+}
+
+// flags are applied on top of the config file
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<(Config, Mode), String> {
+    let args = args.into_iter().collect::<Vec<_>>();
+    let config_path = args
+        .iter()
+        .position(|arg| arg == "--config")
+        .map(|index| {
+            args.get(index + 1)
+                .map(PathBuf::from)
+                .ok_or_else(|| "missing value for --config".to_owned())
+        })
+        .transpose()?;
+    let mut config = match config_path {
+        Some(path) => get_config(path)?,
+        None if get_path("config.ron").exists() => get_config(get_path("config.ron"))?,
+        None => Config::default(),
+    };
+
+    let mut mode = Mode::Run;
+    let mut textures = None;
+    let mut args = args.into_iter().peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => {
+                args.next();
+            }
+            "--backend" => {
+                config.backends = parse::<String>(&arg, args.next())?
+                    .split(',')
+                    .map(|backend| backend.trim().parse())
+                    .collect::<Result<_, _>>()?
+            }
+            "--adapter" => {
+                let adapter = parse::<String>(&arg, args.next())?;
+                config.adapter = Some(match adapter.parse() {
+                    Ok(index) => AdapterSelector::Index(index),
+                    Err(_) => AdapterSelector::Name(adapter),
+                });
+            }
+            "--list-adapters" => mode = Mode::ListAdapters,
+            "--power" => {
+                config.power = match args.next().as_deref() {
+                    Some("low") => Power::Low,
+                    Some("high") => Power::High,
+                    value => return Err(format!("invalid value for --power: {:?}", value)),
+                }
+            }
+            "--trace" => {
+                config.trace = true;
+                if args.peek().map_or(false, |value| !value.starts_with("--")) {
+                    config.trace_path = PathBuf::from(args.next().unwrap());
+                }
+            }
+            "--no-trace" => config.trace = false,
+            "--width" => config.window.width = parse(&arg, args.next())?,
+            "--height" => config.window.height = parse(&arg, args.next())?,
+            "--fullscreen" => config.window.fullscreen = true,
+            "--windowed" => config.window.fullscreen = false,
+            "--vsync" => config.present_mode = PresentMode::Vsync,
+            "--present-mode" => {
+                config.present_mode = match args.next().as_deref() {
+                    Some("vsync") => PresentMode::Vsync,
+                    Some("mailbox") => PresentMode::Mailbox,
+                    Some("immediate") => PresentMode::Immediate,
+                    value => return Err(format!("invalid value for --present-mode: {:?}", value)),
+                }
+            }
+            "--samples" => config.sample_count = parse(&arg, args.next())?,
+            "--srgb" => config.srgb = true,
+            "--no-srgb" => config.srgb = false,
+            "--screenshots" => config.screenshots = parse::<String>(&arg, args.next())?.into(),
+            "--record" => {
+                config.record.enabled = true;
+                if args.peek().map_or(false, |value| !value.starts_with("--")) {
+                    config.record.path = PathBuf::from(args.next().unwrap());
+                }
+            }
+            "--record-every" => config.record.every = parse(&arg, args.next())?,
+            "--record-timestep" => config.record.timestep = parse(&arg, args.next())?,
+            "--record-frames" => config.record.frames = Some(parse(&arg, args.next())?),
+            "--timestep" => config.timestep = Some(parse(&arg, args.next())?),
+            "--seed" => config.scene.seed = Some(parse(&arg, args.next())?),
+            "--scene" => config.scene = get_scene(parse::<String>(&arg, args.next())?)?,
+            "--entities" => config.scene.entities = parse(&arg, args.next())?,
+            "--texture" => textures
+                .get_or_insert_with(Vec::new)
+                .push(parse::<String>(&arg, args.next())?),
+            "--help" | "-h" => mode = Mode::Help,
+            "--bench" => {
+                mode = Mode::Bench;
+                if args.peek().map_or(false, |value| !value.starts_with("--")) {
+                    config.bench.counts = args
+                        .next()
+                        .unwrap()
+                        .split(',')
+                        .map(|count| parse(&arg, Some(count.trim().to_owned())))
+                        .collect::<Result<_, _>>()?;
+                }
+            }
+            "--bench-frames" => config.bench.frames = parse(&arg, args.next())?,
+            "--bench-output" => config.bench.output = parse::<String>(&arg, args.next())?.into(),
+            "--scenario" => {
+                let mut paths = Vec::new();
+                while args.peek().map_or(false, |value| !value.starts_with("--")) {
+                    paths.push(args.next().unwrap());
+                }
+                mode = Mode::Scenarios(paths);
+            }
+            _ => return Err(format!("unknown option {}", arg)),
+        }
+    }
+    if let Some(textures) = textures {
+        config.scene.textures = textures;
+    }
+    Ok((config, mode))
+}
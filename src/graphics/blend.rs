@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+use wgpu::{BlendDescriptor, BlendFactor, BlendOperation};
+
+
+// variants are declared in the order batches are drawn in
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum BlendMode {
+    Opaque,
+    Alpha,
+    PremultipliedAlpha,
+    Multiply,
+    Additive,
+}
+impl BlendMode {
+    pub fn color_blend(&self) -> BlendDescriptor {
+        let (src_factor, dst_factor) = match self {
+            BlendMode::Opaque => (BlendFactor::One, BlendFactor::Zero),
+            BlendMode::Alpha => (BlendFactor::SrcAlpha, BlendFactor::OneMinusSrcAlpha),
+            BlendMode::PremultipliedAlpha => (BlendFactor::One, BlendFactor::OneMinusSrcAlpha),
+            // dst * lerp(1, src, a) with the shader premultiplying src
+            BlendMode::Multiply => (BlendFactor::DstColor, BlendFactor::OneMinusSrcAlpha),
+            BlendMode::Additive => (BlendFactor::SrcAlpha, BlendFactor::One),
+        };
+        BlendDescriptor {
+            src_factor,
+            dst_factor,
+            operation: BlendOperation::Add,
+        }
+    }
+
+    pub fn alpha_blend(&self) -> BlendDescriptor {
+        let (src_factor, dst_factor) = match self {
+            BlendMode::Opaque => (BlendFactor::One, BlendFactor::Zero),
+            BlendMode::Alpha => (BlendFactor::One, BlendFactor::One),
+            BlendMode::PremultipliedAlpha => (BlendFactor::One, BlendFactor::OneMinusSrcAlpha),
+            BlendMode::Multiply | BlendMode::Additive => (BlendFactor::Zero, BlendFactor::One),
+        };
+        BlendDescriptor {
+            src_factor,
+            dst_factor,
+            operation: BlendOperation::Add,
+        }
+    }
+
+    // straight alpha output has to be premultiplied by the shader for these modes
+    pub fn premultiply(&self) -> bool { matches!(self, BlendMode::Multiply) }
+}
+impl Default for BlendMode {
+    fn default() -> Self { BlendMode::Alpha }
+}
@@ -0,0 +1,289 @@
+use std::{
+    collections::VecDeque,
+    future::Future,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        mpsc::{channel, Sender},
+        Mutex,
+    },
+    thread::JoinHandle,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use futures::{executor::block_on, FutureExt};
+use image::{ImageResult, RgbaImage};
+use serde::{Deserialize, Serialize};
+use wgpu::{
+    Buffer, BufferAsyncError, BufferCopyView, BufferDescriptor, BufferUsage, CommandEncoder,
+    Device, Extent3d, Maintain, MapMode, Origin3d, Texture, TextureCopyView, TextureDataLayout,
+    TextureFormat, COPY_BYTES_PER_ROW_ALIGNMENT,
+};
+
+
+// readbacks waiting on the gpu before recording waits for the oldest one
+const MAX_PENDING_FRAMES: usize = 3;
+
+type Mapping = Pin<Box<dyn Future<Output = Result<(), BufferAsyncError>> + Send>>;
+
+// a texture copied into a mappable buffer with rows padded to COPY_BYTES_PER_ROW_ALIGNMENT
+pub struct Readback {
+    buffer:        Buffer,
+    width:         u32,
+    height:        u32,
+    bytes_per_row: u32,
+    bgra:          bool,
+    mapping:       Option<Mapping>,
+}
+impl Readback {
+    pub fn copy(
+        device: &Device, encoder: &mut CommandEncoder, texture: &Texture, format: TextureFormat,
+        width: u32, height: u32,
+    ) -> Self {
+        let bytes_per_row = (width * 4 + COPY_BYTES_PER_ROW_ALIGNMENT - 1)
+            / COPY_BYTES_PER_ROW_ALIGNMENT
+            * COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label:              Some("readback"),
+            size:               (bytes_per_row * height) as u64,
+            usage:              BufferUsage::COPY_DST | BufferUsage::MAP_READ,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            TextureCopyView {
+                texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+            },
+            BufferCopyView {
+                buffer: &buffer,
+                layout: TextureDataLayout {
+                    offset: 0,
+                    bytes_per_row,
+                    rows_per_image: height,
+                },
+            },
+            Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+        );
+        Self {
+            buffer,
+            width,
+            height,
+            bytes_per_row,
+            bgra: matches!(
+                format,
+                TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+            ),
+            mapping: None,
+        }
+    }
+
+    // starts mapping the buffer, the copy has to be submitted first
+    pub fn map(&mut self) {
+        if self.mapping.is_none() {
+            self.mapping = Some(Box::pin(self.buffer.slice(..).map_async(MapMode::Read)));
+        }
+    }
+
+    // returns the image once the buffer is mapped without waiting for the gpu
+    pub fn poll(&mut self) -> Option<Result<RgbaImage, BufferAsyncError>> {
+        let mapped = self.mapping.as_mut()?.as_mut().now_or_never()?;
+        self.mapping = None;
+        Some(mapped.map(|_| self.take_image()))
+    }
+
+    // blocks until the copy is finished
+    pub fn read(mut self, device: &Device) -> Option<RgbaImage> {
+        self.map();
+        device.poll(Maintain::Wait);
+        block_on(self.mapping.take().unwrap()).ok()?;
+        Some(self.take_image())
+    }
+
+    fn take_image(&self) -> RgbaImage {
+        let image = self.to_image(&self.buffer.slice(..).get_mapped_range());
+        self.buffer.unmap();
+        image
+    }
+
+    // strips the row padding and swizzles bgra to rgba
+    fn to_image(&self, data: &[u8]) -> RgbaImage {
+        let row_size = (self.width * 4) as usize;
+        let mut pixels = Vec::with_capacity(row_size * self.height as usize);
+        for row in data
+            .chunks(self.bytes_per_row as usize)
+            .take(self.height as usize)
+        {
+            pixels.extend_from_slice(&row[..row_size]);
+        }
+        if self.bgra {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        RgbaImage::from_raw(self.width, self.height, pixels).unwrap()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecordConfig {
+    // start recording on launch instead of with F11
+    pub enabled:  bool,
+    pub path:     PathBuf,
+    // every nth rendered frame is captured
+    pub every:    u32,
+    // simulated seconds per rendered frame while recording
+    pub timestep: f32,
+    // stops recording after this many captured frames
+    pub frames:   Option<u32>,
+}
+impl Default for RecordConfig {
+    fn default() -> Self {
+        Self {
+            enabled:  false,
+            path:     PathBuf::from("./recording"),
+            every:    1,
+            timestep: 1.0 / 60.0,
+            frames:   None,
+        }
+    }
+}
+
+struct RecorderQueue {
+    pending: VecDeque<(u32, Readback)>,
+    sender:  Sender<(u32, RgbaImage)>,
+}
+
+// captures frames into numbered pngs written from a background thread
+pub struct Recorder {
+    config:   RecordConfig,
+    frame:    AtomicU32,
+    captured: AtomicU32,
+    queue:    Mutex<RecorderQueue>,
+    writer:   JoinHandle<()>,
+}
+impl Recorder {
+    pub fn new(config: &RecordConfig) -> std::io::Result<Self> {
+        std::fs::create_dir_all(&config.path)?;
+        let (sender, receiver) = channel::<(u32, RgbaImage)>();
+        let path = config.path.clone();
+        let writer = std::thread::Builder::new()
+            .name("recorder".to_owned())
+            .spawn(move || {
+                for (index, image) in receiver {
+                    let frame = path.join(format!("frame_{:06}.png", index));
+                    if let Err(e) = image.save(&frame) {
+                        tracing::error!("failed to save {:?}: {}", frame, e);
+                    }
+                }
+            })?;
+        Ok(Self {
+            config: config.clone(),
+            frame: 0.into(),
+            captured: 0.into(),
+            queue: Mutex::new(RecorderQueue {
+                pending: VecDeque::new(),
+                sender,
+            }),
+            writer,
+        })
+    }
+
+    pub fn timestep(&self) -> f32 { self.config.timestep }
+
+    pub fn captured(&self) -> u32 { self.captured.load(Ordering::Acquire) }
+
+    pub fn is_finished(&self) -> bool {
+        self.config
+            .frames
+            .map_or(false, |frames| self.captured() >= frames)
+    }
+
+    // counts the rendered frame and returns whether it is captured, waits for the oldest readback
+    // while too many are pending so recorded frames stay contiguous
+    pub fn next_frame(&self, device: &Device) -> bool {
+        if self.is_finished()
+            || self.frame.fetch_add(1, Ordering::AcqRel) % self.config.every.max(1) != 0
+        {
+            return false;
+        }
+        let mut queue = self.queue.lock().unwrap();
+        if queue.pending.len() >= MAX_PENDING_FRAMES {
+            let (index, readback) = queue.pending.pop_front().unwrap();
+            match readback.read(device) {
+                Some(image) => {
+                    let _ = queue.sender.send((index, image));
+                }
+                None => tracing::error!("failed to read back recorded frame {}", index),
+            }
+        }
+        true
+    }
+
+    pub fn push(&self, mut readback: Readback) {
+        readback.map();
+        let index = self.captured.fetch_add(1, Ordering::AcqRel);
+        self.queue
+            .lock()
+            .unwrap()
+            .pending
+            .push_back((index, readback));
+    }
+
+    // hands finished readbacks to the writer thread without waiting for the gpu
+    pub fn poll(&self, device: &Device) {
+        device.poll(Maintain::Poll);
+        let mut queue = self.queue.lock().unwrap();
+        loop {
+            let result = match queue
+                .pending
+                .front_mut()
+                .and_then(|(_, readback)| readback.poll())
+            {
+                Some(result) => result,
+                None => break,
+            };
+            let (index, _) = queue.pending.pop_front().unwrap();
+            match result {
+                Ok(image) => {
+                    let _ = queue.sender.send((index, image));
+                }
+                Err(_) => tracing::error!("failed to read back recorded frame {}", index),
+            }
+        }
+    }
+
+    // blocks until every captured frame is written
+    pub fn finish(self, device: &Device) {
+        let queue = self.queue.into_inner().unwrap();
+        for (index, readback) in queue.pending {
+            if let Some(image) = readback.read(device) {
+                let _ = queue.sender.send((index, image));
+            }
+        }
+        drop(queue.sender);
+        if self.writer.join().is_err() {
+            tracing::error!("recorder writer thread panicked");
+        }
+    }
+}
+
+pub fn get_timestamp() -> String {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    format!("{}-{:03}", time.as_secs(), time.subsec_millis())
+}
+
+pub fn save_screenshot(image: &RgbaImage, directory: &Path) -> ImageResult<PathBuf> {
+    std::fs::create_dir_all(directory)?;
+    let path = directory.join(format!("screenshot-{}.png", get_timestamp()));
+    image.save(&path)?;
+    Ok(path)
+}
@@ -0,0 +1,207 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use serde::{Deserialize, Serialize};
+
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorContext {
+    pub system:   String,
+    pub pipeline: Option<String>,
+    pub labels:   Vec<String>,
+}
+impl ErrorContext {
+    pub fn new(system: &str) -> Self {
+        Self {
+            system: system.to_owned(),
+            ..Self::default()
+        }
+    }
+
+    pub fn pipeline(self, pipeline: &str) -> Self {
+        Self {
+            pipeline: Some(pipeline.to_owned()),
+            ..self
+        }
+    }
+
+    pub fn label(mut self, label: &str) -> Self {
+        self.labels.push(label.to_owned());
+        self
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorKind {
+    Validation,
+    OutOfMemory,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorRecord {
+    pub kind:        ErrorKind,
+    pub message:     String,
+    pub context:     ErrorContext,
+    pub frame:       u64,
+    // index of the last action written to the wgpu trace when the error was recorded
+    pub trace_index: Option<usize>,
+    // false for errors that reached the uncaptured error handler
+    pub scoped:      bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ErrorReport {
+    pub seed:   Option<u64>,
+    pub frames: u64,
+    pub trace:  Option<PathBuf>,
+    pub errors: Vec<ErrorRecord>,
+}
+
+pub struct ErrorLog {
+    records:  Mutex<Vec<ErrorRecord>>,
+    contexts: Mutex<Vec<ErrorContext>>,
+    frame:    AtomicU64,
+    dirty:    AtomicBool,
+
+    trace:  Option<PathBuf>,
+    report: Option<PathBuf>,
+    seed:   Option<u64>,
+}
+impl ErrorLog {
+    pub fn new(trace: Option<&Path>, report: Option<&Path>) -> Self {
+        Self {
+            records:  Mutex::new(Vec::new()),
+            contexts: Mutex::new(Vec::new()),
+            frame:    0.into(),
+            dirty:    false.into(),
+
+            trace:  trace.map(Path::to_path_buf),
+            report: report.map(Path::to_path_buf),
+            seed:   None,
+        }
+    }
+
+    // the scene seed is included in reports to reproduce the errors
+    pub fn with_seed(self, seed: u64) -> Self {
+        Self {
+            seed: Some(seed),
+            ..self
+        }
+    }
+
+    pub fn frame(&self) -> u64 { self.frame.load(Ordering::Acquire) }
+
+    pub fn push_context(&self, context: ErrorContext) {
+        self.contexts.lock().unwrap().push(context);
+    }
+
+    pub fn pop_context(&self) -> Option<ErrorContext> { self.contexts.lock().unwrap().pop() }
+
+    // uncaptured errors are attributed to the innermost open scope
+    pub fn current_context(&self) -> ErrorContext {
+        self.contexts
+            .lock()
+            .unwrap()
+            .last()
+            .cloned()
+            .unwrap_or_else(|| ErrorContext::new("uncaptured"))
+    }
+
+    pub fn record(&self, error: &wgpu::Error, context: ErrorContext, scoped: bool) {
+        let (kind, message) = match error {
+            wgpu::Error::ValidationError { description, .. } => {
+                (ErrorKind::Validation, description.clone())
+            }
+            wgpu::Error::OutOfMemoryError { source } => {
+                (ErrorKind::OutOfMemory, source.to_string())
+            }
+        };
+        let record = ErrorRecord {
+            kind,
+            message,
+            context,
+            frame: self.frame(),
+            trace_index: self.trace.as_deref().and_then(get_trace_index),
+            scoped,
+        };
+        tracing::error!(
+            "{:?} error in {} ({:?}) at frame {}: {}",
+            record.kind,
+            record.context.system,
+            record.context.pipeline,
+            record.frame,
+            record.message
+        );
+        self.records.lock().unwrap().push(record);
+        self.dirty.store(true, Ordering::Release);
+    }
+
+    pub fn len(&self) -> usize { self.records.lock().unwrap().len() }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    pub fn records(&self) -> Vec<ErrorRecord> { self.records.lock().unwrap().clone() }
+
+    pub fn query(&self, filter: impl Fn(&ErrorRecord) -> bool) -> Vec<ErrorRecord> {
+        self.records
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|record| filter(record))
+            .cloned()
+            .collect()
+    }
+
+    pub fn by_system(&self, system: &str) -> Vec<ErrorRecord> {
+        self.query(|record| record.context.system == system)
+    }
+
+    pub fn by_pipeline(&self, pipeline: &str) -> Vec<ErrorRecord> {
+        self.query(|record| record.context.pipeline.as_deref() == Some(pipeline))
+    }
+
+    pub fn report(&self) -> ErrorReport {
+        ErrorReport {
+            seed:   self.seed,
+            frames: self.frame(),
+            trace:  self.trace.clone(),
+            errors: self.records(),
+        }
+    }
+
+    pub fn write_report(&self, path: &Path) -> std::io::Result<()> {
+        let report = serde_json::to_string_pretty(&self.report())?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, report)
+    }
+
+    // advances the frame counter and rewrites the report if new errors were recorded
+    pub fn next_frame(&self) {
+        self.frame.fetch_add(1, Ordering::AcqRel);
+        if self.dirty.swap(false, Ordering::AcqRel) {
+            if let Some(path) = self.report.as_deref() {
+                if let Err(error) = self.write_report(path) {
+                    tracing::warn!("failed to write error report {:?}: {}", path, error);
+                }
+            }
+        }
+    }
+}
+
+// counts the top level actions in the trace, each starts unindented on a new line
+fn get_trace_index(path: &Path) -> Option<usize> {
+    let trace = fs::read_to_string(path.join("trace.ron")).ok()?;
+    let count = trace
+        .lines()
+        .filter(|line| line.starts_with(|c: char| c.is_ascii_uppercase()))
+        .count();
+    count.checked_sub(1)
+}
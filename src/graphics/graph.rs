@@ -0,0 +1,578 @@
+use std::{any::TypeId, collections::HashMap};
+
+use wgpu::{
+    Color, CommandEncoder, CommandEncoderDescriptor, Extent3d, LoadOp, Operations,
+    RenderPassColorAttachmentDescriptor, RenderPassDepthStencilAttachmentDescriptor,
+    RenderPassDescriptor, Texture, TextureDescriptor, TextureDimension, TextureFormat,
+    TextureUsage, TextureView, TextureViewDescriptor,
+};
+
+use crate::graphics::{
+    capture::Readback,
+    errors::ErrorContext,
+    pipelines::{BlitPipeline, Pipeline},
+    renderer::Renderer,
+};
+
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Attachment {
+    Frame,
+    Texture(String),
+    // the frame, or the post processing input when effects are enabled
+    Scene,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Viewport {
+    // fractions of the target size with the origin at the top left
+    Normalized {
+        x:      f32,
+        y:      f32,
+        width:  f32,
+        height: f32,
+    },
+    Pixels {
+        x:      u32,
+        y:      u32,
+        width:  u32,
+        height: u32,
+    },
+}
+impl Viewport {
+    pub const FULL: Self = Viewport::Normalized {
+        x:      0.0,
+        y:      0.0,
+        width:  1.0,
+        height: 1.0,
+    };
+
+    // x, y, width and height in pixels clamped to the target
+    pub fn rect(&self, width: u32, height: u32) -> (u32, u32, u32, u32) {
+        let (x, y, w, h) = match *self {
+            Viewport::Normalized {
+                x,
+                y,
+                width: w,
+                height: h,
+            } => (
+                (x * width as f32).round() as u32,
+                (y * height as f32).round() as u32,
+                (w * width as f32).round() as u32,
+                (h * height as f32).round() as u32,
+            ),
+            Viewport::Pixels {
+                x,
+                y,
+                width,
+                height,
+            } => (x, y, width, height),
+        };
+        let (x, y) = (x.min(width), y.min(height));
+        (x, y, w.min(width - x), h.min(height - y))
+    }
+
+    pub fn aspect(&self, width: u32, height: u32) -> f32 {
+        let (_, _, width, height) = self.rect(width, height);
+        width.max(1) as f32 / height.max(1) as f32
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TargetDescriptor {
+    // None uses the swapchain format
+    pub format: Option<TextureFormat>,
+    // size relative to the swapchain
+    pub scale:  f32,
+}
+impl TargetDescriptor {
+    pub fn color() -> Self {
+        Self {
+            format: None,
+            scale:  1.0,
+        }
+    }
+
+    pub fn depth() -> Self {
+        Self {
+            format: Some(TextureFormat::Depth32Float),
+            scale:  1.0,
+        }
+    }
+
+    pub fn with_scale(self, scale: f32) -> Self { Self { scale, ..self } }
+}
+
+pub struct RenderGraphPass {
+    pub name:         String,
+    pub inputs:       Vec<String>,
+    pub color:        Attachment,
+    pub color_load:   LoadOp<Color>,
+    pub depth:        Option<(String, LoadOp<f32>)>,
+    pub pipelines:    Vec<TypeId>,
+    // passes this one runs after besides the writers of its inputs, missing ones are ignored
+    pub after:        Vec<String>,
+    // renders into multisampled targets resolved to the attachment
+    pub multisampled: bool,
+    // restricts drawing to part of the attachment, clearing still covers all of it
+    pub viewport:     Option<Viewport>,
+}
+impl RenderGraphPass {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name:         name.to_owned(),
+            inputs:       Vec::new(),
+            color:        Attachment::Frame,
+            color_load:   LoadOp::Load,
+            depth:        None,
+            pipelines:    Vec::new(),
+            after:        Vec::new(),
+            multisampled: false,
+            viewport:     None,
+        }
+    }
+
+    pub fn input(mut self, target: &str) -> Self {
+        self.inputs.push(target.to_owned());
+        self
+    }
+
+    pub fn output(mut self, target: &str) -> Self {
+        self.color = Attachment::Texture(target.to_owned());
+        self
+    }
+
+    pub fn attachment(mut self, attachment: Attachment) -> Self {
+        self.color = attachment;
+        self
+    }
+
+    pub fn clear(mut self, color: Color) -> Self {
+        self.color_load = LoadOp::Clear(color);
+        self
+    }
+
+    pub fn load(mut self) -> Self {
+        self.color_load = LoadOp::Load;
+        self
+    }
+
+    pub fn depth(mut self, target: &str, load: LoadOp<f32>) -> Self {
+        self.depth = Some((target.to_owned(), load));
+        self
+    }
+
+    pub fn after(mut self, pass: &str) -> Self {
+        self.after.push(pass.to_owned());
+        self
+    }
+
+    pub fn multisampled(mut self) -> Self {
+        self.multisampled = true;
+        self
+    }
+
+    pub fn viewport(mut self, viewport: Viewport) -> Self {
+        self.viewport = Some(viewport);
+        self
+    }
+
+    pub fn pipeline<P: Pipeline + 'static>(mut self) -> Self {
+        self.pipelines.push(TypeId::of::<P>());
+        self
+    }
+
+    fn writes(&self, target: &str, scene: &Attachment) -> bool {
+        let color = match &self.color {
+            Attachment::Scene => scene,
+            color => color,
+        };
+        *color == Attachment::Texture(target.to_owned())
+            || self
+                .depth
+                .as_ref()
+                .map_or(false, |(depth, _)| depth == target)
+    }
+}
+
+pub struct RenderTarget {
+    // unique per created texture, changes whenever the target is recreated
+    pub id:           u64,
+    pub texture:      Texture,
+    pub view:         TextureView,
+    pub size:         Extent3d,
+    pub sample_count: u32,
+    pub msaa:         Option<(Texture, TextureView)>,
+}
+impl RenderTarget {
+    // the view multisampled passes render into, and the view it resolves to
+    fn attachment(&self, multisampled: bool) -> (&TextureView, Option<&TextureView>) {
+        match (&self.msaa, multisampled) {
+            (Some((_, msaa)), true) => (msaa, Some(&self.view)),
+            _ => (&self.view, None),
+        }
+    }
+}
+
+pub struct RenderGraph {
+    pub passes:  Vec<RenderGraphPass>,
+    pub targets: HashMap<String, TargetDescriptor>,
+
+    // where passes writing the scene attachment render to
+    scene:          Attachment,
+    textures:       HashMap<String, RenderTarget>,
+    next_target_id: u64,
+    order:          Option<Vec<usize>>,
+}
+impl RenderGraph {
+    pub fn new() -> Self {
+        Self {
+            passes:         Vec::new(),
+            targets:        HashMap::new(),
+            scene:          Attachment::Frame,
+            textures:       HashMap::new(),
+            next_target_id: 0,
+            order:          None,
+        }
+    }
+
+    pub fn set_scene(&mut self, attachment: Attachment) {
+        if self.scene != attachment {
+            self.scene = attachment;
+            self.order = None;
+        }
+    }
+
+    pub fn add_target(&mut self, name: &str, descriptor: TargetDescriptor) {
+        self.targets.insert(name.to_owned(), descriptor);
+        self.textures.remove(name);
+    }
+
+    pub fn remove_target(&mut self, name: &str) {
+        self.targets.remove(name);
+        self.textures.remove(name);
+    }
+
+    pub fn add_pass(&mut self, pass: RenderGraphPass) {
+        self.passes.push(pass);
+        self.order = None;
+    }
+
+    pub fn pass_mut(&mut self, name: &str) -> Option<&mut RenderGraphPass> {
+        self.order = None;
+        self.passes.iter_mut().find(|pass| pass.name == name)
+    }
+
+    pub fn remove_pass(&mut self, name: &str) {
+        self.passes.retain(|pass| pass.name != name);
+        self.order = None;
+    }
+
+    pub fn target(&self, name: &str) -> Option<&RenderTarget> { self.textures.get(name) }
+
+    pub fn scene(&self) -> &Attachment { &self.scene }
+
+    // passes run after the last earlier pass writing each of their inputs, or after every
+    // later writer if there is none, and after the passes they name, otherwise in insertion order
+    fn resolve(&self) -> Vec<usize> {
+        let count = self.passes.len();
+        let mut dependencies = vec![Vec::new(); count];
+        for (i, pass) in self.passes.iter().enumerate() {
+            for input in pass.inputs.iter() {
+                let writers = self
+                    .passes
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, other)| i != *j && other.writes(input, &self.scene))
+                    .map(|(j, _)| j)
+                    .collect::<Vec<_>>();
+                match writers.iter().filter(|j| **j < i).max() {
+                    Some(j) => dependencies[i].push(*j),
+                    None => dependencies[i].extend(writers),
+                }
+            }
+            for name in pass.after.iter() {
+                dependencies[i].extend(
+                    self.passes
+                        .iter()
+                        .enumerate()
+                        .filter(|(j, other)| i != *j && other.name == *name)
+                        .map(|(j, _)| j),
+                );
+            }
+        }
+
+        let mut order = Vec::with_capacity(count);
+        let mut done = vec![false; count];
+        while order.len() < count {
+            let next = (0..count).find(|i| !done[*i] && dependencies[*i].iter().all(|j| done[*j]));
+            match next {
+                Some(i) => {
+                    done[i] = true;
+                    order.push(i);
+                }
+                None => {
+                    tracing::error!("render graph contains a cycle, using insertion order");
+                    return (0..count).collect();
+                }
+            }
+        }
+        order
+    }
+
+    pub fn update(&mut self, renderer: &Renderer) {
+        if self.order.is_none() {
+            self.order = Some(self.resolve());
+        }
+        self.update_targets(renderer);
+    }
+
+    fn update_targets(&mut self, renderer: &Renderer) {
+        for (name, descriptor) in self.targets.iter() {
+            let size = Extent3d {
+                width:  ((renderer.width as f32 * descriptor.scale) as u32).max(1),
+                height: ((renderer.height as f32 * descriptor.scale) as u32).max(1),
+                depth:  1,
+            };
+            if self.textures.get(name).map_or(false, |target| {
+                target.size == size && target.sample_count == renderer.sample_count
+            }) {
+                continue;
+            }
+            let format = descriptor.format.unwrap_or(renderer.format);
+            let texture = renderer.device.create_texture(&TextureDescriptor {
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage: TextureUsage::RENDER_ATTACHMENT
+                    | TextureUsage::SAMPLED
+                    | TextureUsage::COPY_SRC,
+                label: Some(name),
+            });
+            let view = texture.create_view(&TextureViewDescriptor::default());
+            let msaa = if renderer.sample_count > 1 {
+                let texture = renderer.device.create_texture(&TextureDescriptor {
+                    size,
+                    mip_level_count: 1,
+                    sample_count: renderer.sample_count,
+                    dimension: TextureDimension::D2,
+                    format,
+                    usage: TextureUsage::RENDER_ATTACHMENT,
+                    label: Some(&format!("{} msaa", name)),
+                });
+                let view = texture.create_view(&TextureViewDescriptor::default());
+                Some((texture, view))
+            } else {
+                None
+            };
+            self.next_target_id += 1;
+            self.textures.insert(name.clone(), RenderTarget {
+                id: self.next_target_id,
+                texture,
+                view,
+                size,
+                sample_count: renderer.sample_count,
+                msaa,
+            });
+        }
+    }
+
+    pub fn execute(&mut self, renderer: &Renderer) {
+        let frame = match renderer.frame.as_ref() {
+            Some(frame) => frame,
+            None => return,
+        };
+        self.update(renderer);
+
+        // screenshots and recorded frames redirect the frame into a copyable target
+        let screenshot = renderer.take_screenshot_request();
+        let record = renderer
+            .recorder
+            .as_ref()
+            .map_or(false, |recorder| recorder.next_frame(&renderer.device));
+        let capture = if screenshot || record {
+            Some(renderer.create_capture_target())
+        } else {
+            None
+        };
+
+        let mut encoder = renderer
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("render graph"),
+            });
+        if let Some(timer) = renderer.timer.as_ref() {
+            timer.begin(&mut encoder);
+        }
+        self.encode(
+            renderer,
+            &mut encoder,
+            capture.as_ref().map_or(frame.view(), |(_, view)| view),
+        );
+        if let Some(timer) = renderer.timer.as_ref() {
+            timer.end(&mut encoder);
+        }
+        if let Some((_, view)) = capture.as_ref() {
+            Self::blit(renderer, &mut encoder, view, frame.view());
+        }
+
+        let mut readback = |copy: bool| {
+            capture.as_ref().filter(|_| copy).map(|(texture, _)| {
+                Readback::copy(
+                    &renderer.device,
+                    &mut encoder,
+                    texture,
+                    renderer.format,
+                    renderer.width,
+                    renderer.height,
+                )
+            })
+        };
+        let screenshot = readback(screenshot);
+        let record = readback(record);
+
+        renderer.error_scope(ErrorContext::new("render graph").label("submit"), || {
+            renderer.queue.submit(Some(encoder.finish()));
+        });
+        if let Some(readback) = screenshot {
+            renderer.save_screenshot(readback);
+        }
+        if let Some(recorder) = renderer.recorder.as_ref() {
+            if let Some(readback) = record {
+                recorder.push(readback);
+            }
+            recorder.poll(&renderer.device);
+        }
+    }
+
+    // draws the capture target to the frame
+    fn blit(
+        renderer: &Renderer, encoder: &mut CommandEncoder, source: &TextureView,
+        frame: &TextureView,
+    ) {
+        let mut pipeline = renderer.get_pipeline_mut::<BlitPipeline>();
+        let pipeline = pipeline.downcast_mut::<BlitPipeline>();
+        let context = ErrorContext::new("render graph")
+            .pipeline(std::any::type_name::<BlitPipeline>())
+            .label("blit");
+        renderer.error_scope(context, || {
+            pipeline.prepare(renderer, source);
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                color_attachments:        &[RenderPassColorAttachmentDescriptor {
+                    attachment:     frame,
+                    resolve_target: None,
+                    ops:            Operations {
+                        load:  LoadOp::Clear(Color::BLACK),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+                label:                    Some("blit"),
+            });
+            pipeline.draw(renderer, &mut render_pass, "blit");
+        });
+    }
+
+    fn encode(&self, renderer: &Renderer, encoder: &mut CommandEncoder, frame: &TextureView) {
+        for index in self.order.as_ref().unwrap().iter() {
+            let pass = &self.passes[*index];
+            let color = match &pass.color {
+                Attachment::Scene => &self.scene,
+                color => color,
+            };
+            let ((attachment, resolve_target), size) = match color {
+                Attachment::Texture(name) => match self.textures.get(name) {
+                    Some(target) => (
+                        target.attachment(pass.multisampled),
+                        (target.size.width, target.size.height),
+                    ),
+                    None => {
+                        tracing::error!("render pass {} writes unknown target {}", pass.name, name);
+                        continue;
+                    }
+                },
+                _ => (
+                    match (&renderer.msaa_target, pass.multisampled) {
+                        (Some((_, msaa)), true) => (msaa, Some(frame)),
+                        _ => (frame, None),
+                    },
+                    (renderer.width, renderer.height),
+                ),
+            };
+            let depth = pass
+                .depth
+                .as_ref()
+                .and_then(|(name, load)| self.textures.get(name).map(|target| (target, load)));
+
+            let mut pipelines = pass
+                .pipelines
+                .iter()
+                .filter_map(|id| renderer.take_pipeline(*id).map(|pipeline| (*id, pipeline)))
+                .collect::<Vec<(TypeId, Box<dyn Pipeline>)>>();
+            let context = pipelines.iter().fold(
+                ErrorContext::new("render graph")
+                    .label(&pass.name)
+                    .label(match color {
+                        Attachment::Texture(name) => name,
+                        _ => "frame",
+                    }),
+                |context, (id, _)| context.label(renderer.get_pipeline_name(*id)),
+            );
+            renderer.push_error_scope(match pipelines.as_slice() {
+                [(id, _)] => context.pipeline(renderer.get_pipeline_name(*id)),
+                _ => context,
+            });
+            {
+                let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                    color_attachments:        &[RenderPassColorAttachmentDescriptor {
+                        attachment,
+                        resolve_target,
+                        ops: Operations {
+                            load:  pass.color_load,
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: depth.map(|(target, load)| {
+                        RenderPassDepthStencilAttachmentDescriptor {
+                            attachment:  target.attachment(pass.multisampled).0,
+                            depth_ops:   Some(Operations {
+                                load:  *load,
+                                store: true,
+                            }),
+                            stencil_ops: None,
+                        }
+                    }),
+                    label:                    Some(&pass.name),
+                });
+                // empty viewports are invalid, the pass only clears
+                let visible = match pass.viewport.map(|viewport| viewport.rect(size.0, size.1)) {
+                    Some((_, _, 0, _)) | Some((_, _, _, 0)) => false,
+                    Some((x, y, width, height)) => {
+                        render_pass.set_viewport(
+                            x as f32,
+                            y as f32,
+                            width as f32,
+                            height as f32,
+                            0.0,
+                            1.0,
+                        );
+                        render_pass.set_scissor_rect(x, y, width, height);
+                        true
+                    }
+                    None => true,
+                };
+                if visible {
+                    for (_, pipeline) in pipelines.iter_mut() {
+                        pipeline.draw(renderer, &mut render_pass, &pass.name);
+                    }
+                }
+            }
+            renderer.pop_error_scope();
+            for (id, pipeline) in pipelines {
+                renderer.return_pipeline(id, pipeline);
+            }
+        }
+    }
+}
@@ -1,2 +1,8 @@
+pub mod blend;
+pub mod capture;
+pub mod errors;
+pub mod graph;
 pub mod pipelines;
 pub mod renderer;
+pub mod stats;
+pub mod texture;
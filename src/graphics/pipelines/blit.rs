@@ -0,0 +1,150 @@
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindingResource, ColorStateDescriptor, ColorWrite, CullMode, FrontFace,
+    PipelineLayoutDescriptor, PolygonMode, PrimitiveTopology, ProgrammableStageDescriptor,
+    RasterizationStateDescriptor, RenderPass, RenderPipeline, RenderPipelineDescriptor,
+    ShaderModuleDescriptor, TextureView, VertexStateDescriptor,
+};
+
+use crate::{
+    graphics::{blend::BlendMode, pipelines::Pipeline, renderer::Renderer, texture::SamplerConfig},
+    resources::get_shader,
+};
+
+
+// draws a texture the size of the frame to the frame, used for captured frames
+pub struct BlitPipeline {
+    pub bind_group_layout: BindGroupLayout,
+    pub pipeline:          RenderPipeline,
+    pub bind_group:        Option<BindGroup>,
+}
+impl BlitPipeline {
+    pub fn prepare(&mut self, renderer: &Renderer, source: &TextureView) {
+        self.bind_group = Some(renderer.device.create_bind_group(&BindGroupDescriptor {
+            layout:  &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding:  0,
+                    resource: BindingResource::TextureView(source),
+                },
+                BindGroupEntry {
+                    binding:  1,
+                    resource: BindingResource::Sampler(
+                        renderer.get_sampler(SamplerConfig::NEAREST),
+                    ),
+                },
+            ],
+            label:   None,
+        }));
+    }
+}
+impl Pipeline for BlitPipeline {
+    fn new(renderer: &Renderer) -> Self {
+        let bind_group_layout =
+            renderer
+                .device
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding:    0,
+                            visibility: wgpu::ShaderStage::FRAGMENT,
+                            ty:         wgpu::BindingType::Texture {
+                                multisampled:   false,
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                sample_type:    wgpu::TextureSampleType::Float { filterable: true },
+                            },
+                            count:      None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding:    1,
+                            visibility: wgpu::ShaderStage::FRAGMENT,
+                            ty:         wgpu::BindingType::Sampler {
+                                comparison: false,
+                                filtering:  true,
+                            },
+                            count:      None,
+                        },
+                    ],
+                    label:   None,
+                });
+
+        let pipeline_layout = renderer
+            .device
+            .create_pipeline_layout(&PipelineLayoutDescriptor {
+                bind_group_layouts:   &[&bind_group_layout],
+                push_constant_ranges: &[],
+                label:                None,
+            });
+
+        let vs_module = renderer
+            .device
+            .create_shader_module(&ShaderModuleDescriptor {
+                source: wgpu::ShaderSource::SpirV(
+                    get_shader("post/fullscreen.vert").unwrap().into(),
+                ),
+                label:  None,
+                flags:  wgpu::ShaderFlags::empty(),
+            });
+        let fs_module = renderer
+            .device
+            .create_shader_module(&ShaderModuleDescriptor {
+                source: wgpu::ShaderSource::SpirV(get_shader("post/blit.frag").unwrap().into()),
+                label:  None,
+                flags:  wgpu::ShaderFlags::empty(),
+            });
+
+        let pipeline = renderer
+            .device
+            .create_render_pipeline(&RenderPipelineDescriptor {
+                layout:                    Some(&pipeline_layout),
+                vertex_stage:              ProgrammableStageDescriptor {
+                    module:      &vs_module,
+                    entry_point: "main",
+                },
+                fragment_stage:            Some(ProgrammableStageDescriptor {
+                    module:      &fs_module,
+                    entry_point: "main",
+                }),
+                rasterization_state:       Some(RasterizationStateDescriptor {
+                    front_face:             FrontFace::Ccw,
+                    cull_mode:              CullMode::None,
+                    depth_bias:             0,
+                    depth_bias_slope_scale: 0.0,
+                    depth_bias_clamp:       0.0,
+                    clamp_depth:            false,
+                    polygon_mode:           PolygonMode::Fill,
+                }),
+                primitive_topology:        PrimitiveTopology::TriangleList,
+                color_states:              &[ColorStateDescriptor {
+                    format:      renderer.format,
+                    color_blend: BlendMode::Opaque.color_blend(),
+                    alpha_blend: BlendMode::Opaque.alpha_blend(),
+                    write_mask:  ColorWrite::ALL,
+                }],
+                depth_stencil_state:       None,
+                sample_count:              1,
+                alpha_to_coverage_enabled: false,
+                sample_mask:               !0,
+                vertex_state:              VertexStateDescriptor {
+                    index_format:   None,
+                    vertex_buffers: &[],
+                },
+                label:                     Some("blit"),
+            });
+
+        Self {
+            bind_group_layout,
+            pipeline,
+            bind_group: None,
+        }
+    }
+
+    // not counted as a draw call, it only shows the already rendered capture target
+    fn draw<'s>(&'s mut self, _renderer: &'s Renderer, pass: &mut RenderPass<'s>, _name: &str) {
+        if let Some(bind_group) = self.bind_group.as_ref() {
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+    }
+}
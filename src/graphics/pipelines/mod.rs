@@ -1,9 +1,99 @@
+use wgpu::{Features, Limits, RenderPass};
+
 use crate::graphics::renderer::Renderer;
 
+// optional features and limits are requested when the adapter supports them,
+// pipelines check the device to select a fallback path otherwise
+#[derive(Clone, Debug)]
+pub struct Requirements {
+    pub features:          Features,
+    pub optional_features: Features,
+    pub limits:            Limits,
+    pub optional_limits:   Limits,
+}
+impl Requirements {
+    pub fn combine(self, other: Self) -> Self {
+        Self {
+            features:          self.features | other.features,
+            optional_features: self.optional_features | other.optional_features,
+            limits:            map_limits(&self.limits, &other.limits, u32::max),
+            optional_limits:   map_limits(&self.optional_limits, &other.optional_limits, u32::max),
+        }
+    }
+}
+impl Default for Requirements {
+    fn default() -> Self {
+        Self {
+            features:          Features::empty(),
+            optional_features: Features::empty(),
+            limits:            Limits::default(),
+            optional_limits:   Limits::default(),
+        }
+    }
+}
+
+pub fn map_limits(a: &Limits, b: &Limits, f: impl Fn(u32, u32) -> u32) -> Limits {
+    Limits {
+        max_bind_groups:                                 f(a.max_bind_groups, b.max_bind_groups),
+        max_dynamic_uniform_buffers_per_pipeline_layout: f(
+            a.max_dynamic_uniform_buffers_per_pipeline_layout,
+            b.max_dynamic_uniform_buffers_per_pipeline_layout,
+        ),
+        max_dynamic_storage_buffers_per_pipeline_layout: f(
+            a.max_dynamic_storage_buffers_per_pipeline_layout,
+            b.max_dynamic_storage_buffers_per_pipeline_layout,
+        ),
+        max_sampled_textures_per_shader_stage:           f(
+            a.max_sampled_textures_per_shader_stage,
+            b.max_sampled_textures_per_shader_stage,
+        ),
+        max_samplers_per_shader_stage:                   f(
+            a.max_samplers_per_shader_stage,
+            b.max_samplers_per_shader_stage,
+        ),
+        max_storage_buffers_per_shader_stage:            f(
+            a.max_storage_buffers_per_shader_stage,
+            b.max_storage_buffers_per_shader_stage,
+        ),
+        max_storage_textures_per_shader_stage:           f(
+            a.max_storage_textures_per_shader_stage,
+            b.max_storage_textures_per_shader_stage,
+        ),
+        max_uniform_buffers_per_shader_stage:            f(
+            a.max_uniform_buffers_per_shader_stage,
+            b.max_uniform_buffers_per_shader_stage,
+        ),
+        max_uniform_buffer_binding_size:                 f(
+            a.max_uniform_buffer_binding_size,
+            b.max_uniform_buffer_binding_size,
+        ),
+        max_push_constant_size:                          f(
+            a.max_push_constant_size,
+            b.max_push_constant_size,
+        ),
+    }
+}
+
+// true if every limit in a is within the supported limit in b
+pub fn limits_within(a: &Limits, b: &Limits) -> bool { map_limits(a, b, u32::min) == *a }
+
+// combined requirements of every pipeline the renderer can create
+pub fn requirements() -> Requirements {
+    SpritePipeline::requirements().combine(PostProcessPipeline::requirements())
+}
+
 pub trait Pipeline: Send + Sync {
     fn new(renderer: &Renderer) -> Self
     where
         Self: Sized;
+
+    fn requirements() -> Requirements
+    where
+        Self: Sized, {
+        Requirements::default()
+    }
+
+    fn draw<'s>(&'s mut self, renderer: &'s Renderer, pass: &mut RenderPass<'s>, name: &str);
 }
 impl dyn Pipeline {
     pub fn downcast_mut<P: Pipeline>(&mut self) -> &mut P {
@@ -11,5 +101,9 @@ impl dyn Pipeline {
     }
 }
 
+pub mod blit;
+pub mod post;
 pub mod sprite;
+pub use blit::*;
+pub use post::*;
 pub use sprite::*;
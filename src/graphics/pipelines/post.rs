@@ -0,0 +1,456 @@
+use std::{collections::HashMap, mem::size_of};
+
+use bytemuck::{bytes_of, Pod, Zeroable};
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use ultraviolet::Vec4;
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindingResource, BufferSize, BufferUsage, Color, ColorStateDescriptor, ColorWrite, CullMode,
+    FrontFace, PipelineLayout, PipelineLayoutDescriptor, PolygonMode, PrimitiveTopology,
+    ProgrammableStageDescriptor, RasterizationStateDescriptor, RenderPass, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModule, ShaderModuleDescriptor, TextureViewDescriptor,
+    VertexStateDescriptor,
+};
+
+use crate::{
+    graphics::{
+        blend::BlendMode,
+        graph::{Attachment, RenderGraph, RenderGraphPass, TargetDescriptor},
+        pipelines::Pipeline,
+        renderer::Renderer,
+        texture::{SamplerConfig, TextureConfig},
+    },
+    resources::get_shader,
+};
+
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Effect {
+    // lut is a 256x16 strip of 16 blue slices in data/images
+    ColorGrading {
+        lut:      String,
+        strength: f32,
+    },
+    Vignette {
+        intensity: f32,
+        radius:    f32,
+        softness:  f32,
+    },
+    Crt {
+        scanlines: f32,
+        intensity: f32,
+        curvature: f32,
+    },
+    Pixelate {
+        size: f32,
+    },
+    Bloom {
+        threshold: f32,
+        intensity: f32,
+        radius:    f32,
+    },
+}
+impl Effect {
+    pub fn shader(&self) -> &'static str {
+        match self {
+            Effect::ColorGrading { .. } => "post/color_grading.frag",
+            Effect::Vignette { .. } => "post/vignette.frag",
+            Effect::Crt { .. } => "post/crt.frag",
+            Effect::Pixelate { .. } => "post/pixelate.frag",
+            Effect::Bloom { .. } => "post/bloom.frag",
+        }
+    }
+
+    pub fn params(&self) -> Vec4 {
+        match self {
+            Effect::ColorGrading { strength, .. } => Vec4::new(*strength, 0.0, 0.0, 0.0),
+            Effect::Vignette {
+                intensity,
+                radius,
+                softness,
+            } => Vec4::new(*intensity, *radius, *softness, 0.0),
+            Effect::Crt {
+                scanlines,
+                intensity,
+                curvature,
+            } => Vec4::new(*scanlines, *intensity, *curvature, 0.0),
+            Effect::Pixelate { size } => Vec4::new(*size, 0.0, 0.0, 0.0),
+            Effect::Bloom {
+                threshold,
+                intensity,
+                radius,
+            } => Vec4::new(*threshold, *intensity, *radius, 0.0),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PostEffect {
+    pub enabled: bool,
+    pub effect:  Effect,
+}
+
+// effects run in order on the rendered scene before it is presented
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PostProcess {
+    pub effects: Vec<PostEffect>,
+}
+impl PostProcess {
+    pub fn enabled(&self) -> impl Iterator<Item = &Effect> {
+        self.effects
+            .iter()
+            .filter(|effect| effect.enabled)
+            .map(|effect| &effect.effect)
+    }
+}
+impl Default for PostProcess {
+    fn default() -> Self {
+        let effects = vec![
+            Effect::ColorGrading {
+                lut:      "lut_neutral.png".to_owned(),
+                strength: 1.0,
+            },
+            Effect::Bloom {
+                threshold: 0.8,
+                intensity: 0.6,
+                radius:    2.0,
+            },
+            Effect::Pixelate { size: 4.0 },
+            Effect::Crt {
+                scanlines: 240.0,
+                intensity: 0.25,
+                curvature: 0.1,
+            },
+            Effect::Vignette {
+                intensity: 0.5,
+                radius:    0.75,
+                softness:  0.45,
+            },
+        ];
+        Self {
+            effects: effects
+                .into_iter()
+                .map(|effect| PostEffect {
+                    enabled: false,
+                    effect,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
+#[repr(C, align(16))]
+pub struct EffectData {
+    pub params:     Vec4,
+    // width, height, 1 / width, 1 / height
+    pub resolution: Vec4,
+}
+
+pub struct PostProcessStep {
+    pub effect:      Effect,
+    pub input:       String,
+    pub data_buffer: u64,
+    pub bind_group:  Option<BindGroup>,
+    // input target id and lut texture the bind group was created with
+    pub bound:       Option<(u64, u64)>,
+}
+
+pub struct PostProcessPipeline {
+    pub bind_group_layout: BindGroupLayout,
+
+    pub pipeline_layout: PipelineLayout,
+    pub vs_module:       ShaderModule,
+    // shader -> pipeline
+    pub pipelines:       HashMap<&'static str, RenderPipeline>,
+
+    pub default_lut: u64,
+
+    // pass name -> step
+    pub steps:      HashMap<String, PostProcessStep>,
+    // effects the render graph was last configured with
+    pub configured: Option<Vec<Effect>>,
+}
+impl PostProcessPipeline {
+    // the last effect, writing the frame
+    pub const OUTPUT_PASS: &'static str = "post output";
+    pub const SCENE_TARGET: &'static str = "scene";
+    pub const TARGETS: [&'static str; 2] = ["post_a", "post_b"];
+
+    fn pass_name(index: usize, count: usize) -> String {
+        if index + 1 == count {
+            Self::OUTPUT_PASS.to_owned()
+        } else {
+            format!("post {}", index)
+        }
+    }
+
+    fn create_render_pipeline(&self, renderer: &Renderer, shader: &str) -> RenderPipeline {
+        let fs_module = renderer
+            .device
+            .create_shader_module(&ShaderModuleDescriptor {
+                source: wgpu::ShaderSource::SpirV(get_shader(shader).unwrap().into()),
+                label:  None,
+                flags:  wgpu::ShaderFlags::empty(),
+            });
+        renderer
+            .device
+            .create_render_pipeline(&RenderPipelineDescriptor {
+                layout:                    Some(&self.pipeline_layout),
+                vertex_stage:              ProgrammableStageDescriptor {
+                    module:      &self.vs_module,
+                    entry_point: "main",
+                },
+                fragment_stage:            Some(ProgrammableStageDescriptor {
+                    module:      &fs_module,
+                    entry_point: "main",
+                }),
+                rasterization_state:       Some(RasterizationStateDescriptor {
+                    front_face:             FrontFace::Ccw,
+                    cull_mode:              CullMode::None,
+                    depth_bias:             0,
+                    depth_bias_slope_scale: 0.0,
+                    depth_bias_clamp:       0.0,
+                    clamp_depth:            false,
+                    polygon_mode:           PolygonMode::Fill,
+                }),
+                primitive_topology:        PrimitiveTopology::TriangleList,
+                color_states:              &[ColorStateDescriptor {
+                    format:      renderer.format,
+                    color_blend: BlendMode::Opaque.color_blend(),
+                    alpha_blend: BlendMode::Opaque.alpha_blend(),
+                    write_mask:  ColorWrite::ALL,
+                }],
+                depth_stencil_state:       None,
+                sample_count:              1,
+                alpha_to_coverage_enabled: false,
+                sample_mask:               !0,
+                vertex_state:              VertexStateDescriptor {
+                    index_format:   None,
+                    vertex_buffers: &[],
+                },
+                label:                     Some(shader),
+            })
+    }
+
+    // routes the scene passes through the enabled effects, the last one writes the frame
+    pub fn configure(&mut self, renderer: &Renderer, graph: &mut RenderGraph, post: &PostProcess) {
+        let effects = post.enabled().cloned().collect::<Vec<_>>();
+        if self.configured.as_ref() == Some(&effects) {
+            return;
+        }
+
+        for name in self.steps.keys() {
+            graph.remove_pass(name);
+        }
+        for step in self.steps.drain().map(|(_, step)| step) {
+            renderer.unload_buffer(step.data_buffer);
+        }
+
+        // without effects the scene is drawn straight to the frame and no targets are kept,
+        // otherwise every effect but the last writes the next ping-pong target
+        graph.set_scene(if effects.is_empty() {
+            Attachment::Frame
+        } else {
+            Attachment::Texture(Self::SCENE_TARGET.to_owned())
+        });
+        let targets = std::iter::once(Self::SCENE_TARGET).chain(Self::TARGETS.iter().copied());
+        for (index, target) in targets.enumerate() {
+            if index >= effects.len() {
+                graph.remove_target(target);
+            } else if !graph.targets.contains_key(target) {
+                graph.add_target(target, TargetDescriptor::color());
+            }
+        }
+
+        let mut input = Self::SCENE_TARGET;
+        for (index, effect) in effects.iter().enumerate() {
+            let name = Self::pass_name(index, effects.len());
+            let mut pass = RenderGraphPass::new(&name)
+                .input(input)
+                .clear(Color::BLACK)
+                .pipeline::<PostProcessPipeline>();
+            let output = Self::TARGETS[index % 2];
+            if index + 1 < effects.len() {
+                pass = pass.output(output);
+            }
+            graph.add_pass(pass);
+
+            #[allow(clippy::map_entry)]
+            if !self.pipelines.contains_key(effect.shader()) {
+                let pipeline = self.create_render_pipeline(renderer, effect.shader());
+                self.pipelines.insert(effect.shader(), pipeline);
+            }
+            let data_buffer =
+                renderer.load_buffer::<EffectData>(1, BufferUsage::UNIFORM | BufferUsage::COPY_DST);
+            self.steps.insert(name, PostProcessStep {
+                effect:      effect.clone(),
+                input:       input.to_owned(),
+                data_buffer: *data_buffer.key(),
+                bind_group:  None,
+                bound:       None,
+            });
+            input = output;
+        }
+
+        self.configured = Some(effects);
+    }
+
+    // rebuilds bind groups only when an input target was recreated or the lut changed
+    pub fn prepare(&mut self, renderer: &Renderer, graph: &RenderGraph) {
+        let layout = &self.bind_group_layout;
+        let default_lut = self.default_lut;
+        for step in self.steps.values_mut() {
+            let target = match graph.target(&step.input) {
+                Some(target) => target,
+                None => continue,
+            };
+            let lut = match &step.effect {
+                Effect::ColorGrading { lut, .. } => *renderer.load_texture(lut).key(),
+                _ => default_lut,
+            };
+            if step.bound == Some((target.id, lut)) {
+                continue;
+            }
+
+            let (width, height) = (target.size.width as f32, target.size.height as f32);
+            renderer.write_buffer(
+                renderer.get_buffer(step.data_buffer),
+                0,
+                bytes_of(&EffectData {
+                    params:     step.effect.params(),
+                    resolution: Vec4::new(width, height, 1.0 / width, 1.0 / height),
+                }),
+            );
+            let lut_view = renderer
+                .get_texture(lut)
+                .create_view(&TextureViewDescriptor::default());
+            step.bind_group = Some(renderer.device.create_bind_group(&BindGroupDescriptor {
+                layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding:  0,
+                        resource: BindingResource::Buffer {
+                            buffer: renderer.get_buffer(step.data_buffer),
+                            offset: 0,
+                            size:   BufferSize::new(size_of::<EffectData>() as u64),
+                        },
+                    },
+                    BindGroupEntry {
+                        binding:  1,
+                        resource: BindingResource::TextureView(&target.view),
+                    },
+                    BindGroupEntry {
+                        binding:  2,
+                        resource: BindingResource::Sampler(
+                            renderer.get_sampler(SamplerConfig::LINEAR),
+                        ),
+                    },
+                    BindGroupEntry {
+                        binding:  3,
+                        resource: BindingResource::TextureView(&lut_view),
+                    },
+                ],
+                label: None,
+            }));
+            step.bound = Some((target.id, lut));
+        }
+    }
+}
+impl Pipeline for PostProcessPipeline {
+    fn new(renderer: &Renderer) -> Self {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStage::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled:   false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type:    wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        let bind_group_layout =
+            renderer
+                .device
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding:    0,
+                            visibility: wgpu::ShaderStage::FRAGMENT,
+                            ty:         wgpu::BindingType::Buffer {
+                                ty:                 wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size:   None,
+                            },
+                            count:      None,
+                        },
+                        texture_entry(1),
+                        wgpu::BindGroupLayoutEntry {
+                            binding:    2,
+                            visibility: wgpu::ShaderStage::FRAGMENT,
+                            ty:         wgpu::BindingType::Sampler {
+                                comparison: false,
+                                filtering:  true,
+                            },
+                            count:      None,
+                        },
+                        texture_entry(3),
+                    ],
+                    label:   None,
+                });
+
+        let pipeline_layout = renderer
+            .device
+            .create_pipeline_layout(&PipelineLayoutDescriptor {
+                bind_group_layouts:   &[&bind_group_layout],
+                push_constant_ranges: &[],
+                label:                None,
+            });
+
+        let vs_module = renderer
+            .device
+            .create_shader_module(&ShaderModuleDescriptor {
+                source: wgpu::ShaderSource::SpirV(
+                    get_shader("post/fullscreen.vert").unwrap().into(),
+                ),
+                label:  None,
+                flags:  wgpu::ShaderFlags::empty(),
+            });
+
+        let default_lut = renderer.load_texture_image(
+            "default lut",
+            RgbaImage::from_pixel(1, 1, Rgba([255, 255, 255, 255])),
+            TextureConfig {
+                linear: true,
+                ..TextureConfig::default()
+            },
+        );
+
+        Self {
+            bind_group_layout,
+
+            pipeline_layout,
+            vs_module,
+            pipelines: HashMap::new(),
+
+            default_lut: *default_lut.key(),
+
+            steps: HashMap::new(),
+            configured: None,
+        }
+    }
+
+    fn draw<'s>(&'s mut self, renderer: &'s Renderer, pass: &mut RenderPass<'s>, name: &str) {
+        let step = match self.steps.get(name) {
+            Some(step) => step,
+            None => return,
+        };
+        if let Some(bind_group) = step.bind_group.as_ref() {
+            pass.set_pipeline(&self.pipelines[step.effect.shader()]);
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.draw(0..3, 0..1);
+            renderer.stats.add_draw_call();
+        }
+    }
+}
@@ -1,46 +1,223 @@
-use std::{collections::HashMap, mem::size_of};
+use std::{collections::HashMap, mem::size_of, num::NonZeroU32};
 
 use bytemuck::bytes_of;
+use image::{Rgba, RgbaImage};
 use itertools::Itertools;
-use shipyard::{IntoFastIter, View};
+use shipyard::{EntityId, Get, IntoFastIter, IntoIter, IntoWithId, View};
 use wgpu::{
-    AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
-    BindGroupLayoutDescriptor, BindingResource, BlendDescriptor, BlendFactor, BlendOperation,
-    BufferSize, BufferUsage, ColorStateDescriptor, ColorWrite, CullMode, FilterMode, FrontFace,
-    IndexFormat, InputStepMode, PipelineLayoutDescriptor, PolygonMode, PrimitiveTopology,
-    ProgrammableStageDescriptor, RasterizationStateDescriptor, RenderPass, RenderPipeline,
-    RenderPipelineDescriptor, Sampler, SamplerDescriptor, ShaderModuleDescriptor, TextureFormat,
-    TextureViewDescriptor, VertexAttributeDescriptor, VertexBufferDescriptor, VertexFormat,
-    VertexStateDescriptor,
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindingResource, BufferSize, BufferUsage, Color, ColorStateDescriptor, ColorWrite, CullMode,
+    Features, FrontFace, IndexFormat, InputStepMode, Limits, PipelineLayout,
+    PipelineLayoutDescriptor, PolygonMode, PrimitiveTopology, ProgrammableStageDescriptor,
+    RasterizationStateDescriptor, RenderPass, RenderPipeline, RenderPipelineDescriptor,
+    ShaderModule, ShaderModuleDescriptor, TextureView, TextureViewDescriptor, TextureViewDimension,
+    VertexAttributeDescriptor, VertexBufferDescriptor, VertexFormat, VertexStateDescriptor,
 };
 
 use crate::{
-    components::{Camera, CameraData, Sprite, Transform},
-    graphics::{pipelines::Pipeline, renderer::Renderer},
+    components::{
+        Camera, CameraData, GlobalTransform, RenderLayers, Sprite, SpriteData, Transform,
+    },
+    graphics::{
+        blend::BlendMode,
+        graph::{Attachment, RenderGraph, RenderGraphPass, Viewport},
+        pipelines::{limits_within, Pipeline, PostProcessPipeline, Requirements},
+        renderer::{get_buffer_size, Renderer},
+        texture::{SamplerConfig, TextureConfig},
+    },
     resources::get_shader,
 };
 
+// must match the array size in sprite/bindless.frag.hlsl
+pub const MAX_BINDING_ARRAY_TEXTURES: usize = 16;
+pub const MAX_TEXTURE_ARRAY_LAYERS: usize = 256;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SpriteTextureMode {
+    // same-sized textures are copied into one D2Array texture per size
+    TextureArray,
+    // textures are bound as a SAMPLED_TEXTURE_BINDING_ARRAY and indexed per instance
+    BindingArray,
+}
+impl SpriteTextureMode {
+    pub fn required_features() -> Features {
+        Features::SAMPLED_TEXTURE_BINDING_ARRAY
+            | Features::SAMPLED_TEXTURE_ARRAY_NON_UNIFORM_INDEXING
+    }
+
+    // the binding array and the palette texture
+    pub fn required_limits() -> Limits {
+        Limits {
+            max_sampled_textures_per_shader_stage: MAX_BINDING_ARRAY_TEXTURES as u32 + 1,
+            ..Limits::default()
+        }
+    }
+
+    pub fn select(features: Features, limits: &Limits) -> Self {
+        if features.contains(Self::required_features())
+            && limits_within(&Self::required_limits(), limits)
+        {
+            SpriteTextureMode::BindingArray
+        } else {
+            SpriteTextureMode::TextureArray
+        }
+    }
+}
+
+pub type SpriteTextureKey = (u64, Option<SamplerConfig>);
+
+pub struct SpriteTextureGroup {
+    pub textures:      Vec<u64>,
+    pub array_texture: Option<u64>,
+    pub bind_group:    BindGroup,
+}
+
+// the graph pass drawing the sprites for one camera
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpriteCameraPass {
+    pub name:     String,
+    pub output:   Attachment,
+    pub clear:    Option<Color>,
+    pub viewport: Viewport,
+    // cameras drawing to the frame run after post processing wrote it
+    pub after:    Option<&'static str>,
+}
+
 pub struct SpritePipeline {
+    pub mode: SpriteTextureMode,
+
     pub bind_group_layout: BindGroupLayout,
 
-    // texture_id -> bind_group
-    pub bind_groups: HashMap<u64, BindGroup>,
+    // (texture_id, sampler) -> (group, layer)
+    pub texture_layers: HashMap<SpriteTextureKey, (usize, u32)>,
+    pub texture_groups: Vec<SpriteTextureGroup>,
 
-    pub pipeline: RenderPipeline,
+    pub pipeline_layout: PipelineLayout,
+    pub vs_module:       ShaderModule,
+    pub fs_module:       ShaderModule,
+    pub pipelines:       HashMap<BlendMode, RenderPipeline>,
+    // sample count the pipelines were created with, they're recreated when it changes
+    pub sample_count:    u32,
 
-    pub camera_buffer: u64,
+    // camera data for every camera pass, bound with a dynamic offset
+    pub camera_buffer:   u64,
+    pub camera_capacity: u64,
+    // pass name -> (camera offset, camera layers)
+    pub cameras:         HashMap<String, (u32, RenderLayers)>,
+    pub configured:      Option<Vec<SpriteCameraPass>>,
+
+    // rows are palettes, sampled by the luminance of the sprite texture
+    pub palette_texture: u64,
 
     pub vertex_buffer:      u64,
     pub vertex_buffer_data: Vec<u8>,
     pub vertex_buffer_size: u64,
 
-    pub texture_sampler: Sampler,
-
-    // vbuf_start, vbuf_length, texture_id
-    pub draw_instances: Vec<(u32, u32, u64)>,
+    // vbuf_start, vbuf_end, group, blend, layers
+    // shared by every camera, each draws the batches on its layers
+    pub draw_instances: Vec<(u32, u32, usize, BlendMode, RenderLayers)>,
 }
 impl SpritePipeline {
-    pub const STRIDE: u64 = 64;
+    pub const STRIDE: u64 = (size_of::<Transform>() + size_of::<SpriteData>()) as u64;
+
+    pub fn pass_name(entity: EntityId) -> String { format!("sprites {:?}", entity) }
+
+    // sprites are drawn sorted by this key and in storage order within a key, later ones on top
+    pub fn draw_order(
+        &self, sprite: &Sprite, layers: RenderLayers,
+    ) -> (BlendMode, usize, RenderLayers) {
+        let group = self
+            .texture_layers
+            .get(&sprite.texture_key())
+            .map_or(0, |(group, _)| *group);
+        (sprite.blend, group, layers)
+    }
+
+    // adds a graph pass per camera in camera order, post processing has to be configured first
+    pub fn configure(&mut self, graph: &mut RenderGraph, cameras: &[(EntityId, &Camera)]) {
+        let scene = graph.scene().clone();
+        // clears ignore the viewport, so only the first camera writing an attachment clears it
+        // and the frame is already filled by the post processing output pass when it's used
+        let mut written = Vec::new();
+        if scene != Attachment::Frame {
+            written.push(Attachment::Frame);
+        }
+        let mut ignored = Vec::new();
+        let passes = cameras
+            .iter()
+            .map(|(entity, camera)| {
+                let output = match &camera.output {
+                    Attachment::Scene => scene.clone(),
+                    output => output.clone(),
+                };
+                let clear = if written.contains(&output) {
+                    if camera.clear.is_some() {
+                        ignored.push(*entity);
+                    }
+                    None
+                } else {
+                    written.push(output);
+                    camera.clear
+                };
+                SpriteCameraPass {
+                    name: Self::pass_name(*entity),
+                    output: camera.output.clone(),
+                    clear,
+                    viewport: camera.viewport,
+                    after: match camera.output {
+                        Attachment::Frame if scene != Attachment::Frame => {
+                            Some(PostProcessPipeline::OUTPUT_PASS)
+                        }
+                        _ => None,
+                    },
+                }
+            })
+            .collect::<Vec<_>>();
+        if self.configured.as_ref() == Some(&passes) {
+            return;
+        }
+        for entity in ignored {
+            tracing::warn!(
+                "camera {:?} clear is ignored, an earlier camera already writes its output",
+                entity
+            );
+        }
+
+        for pass in self.configured.iter().flatten() {
+            graph.remove_pass(&pass.name);
+        }
+        for pass in passes.iter() {
+            let mut graph_pass = RenderGraphPass::new(&pass.name)
+                .attachment(pass.output.clone())
+                .viewport(pass.viewport)
+                .multisampled()
+                .pipeline::<SpritePipeline>();
+            if let Some(after) = pass.after {
+                graph_pass = graph_pass.after(after);
+            }
+            graph.add_pass(match pass.clear {
+                Some(color) => graph_pass.clear(color),
+                None => graph_pass.load(),
+            });
+        }
+        self.configured = Some(passes);
+    }
+
+    pub fn update_camera_count(&mut self, renderer: &Renderer, count: u64) {
+        if count > self.camera_capacity {
+            renderer.unload_buffer(self.camera_buffer);
+            self.camera_capacity = count.next_power_of_two();
+            self.camera_buffer = *renderer
+                .load_buffer::<CameraData>(
+                    self.camera_capacity,
+                    BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+                )
+                .key();
+            // bind groups reference the old camera buffer
+            let textures = self.texture_layers.keys().copied().collect();
+            self.update_texture_groups(renderer, textures);
+        }
+    }
 
     pub fn update_element_count(&mut self, renderer: &Renderer, count: u64) {
         let size = count * Self::STRIDE;
@@ -61,89 +238,422 @@ impl SpritePipeline {
         }
     }
 
+    fn create_bind_group(
+        &self, renderer: &Renderer, textures: BindingResource, sampler: SamplerConfig,
+    ) -> BindGroup {
+        let camera_buffer = renderer.get_buffer(self.camera_buffer);
+        let palette_view = renderer
+            .get_texture(self.palette_texture)
+            .create_view(&TextureViewDescriptor::default());
+        renderer.device.create_bind_group(&BindGroupDescriptor {
+            layout:  &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding:  0,
+                    resource: BindingResource::Buffer {
+                        buffer: &camera_buffer,
+                        offset: 0,
+                        size:   BufferSize::new(size_of::<CameraData>() as u64),
+                    },
+                },
+                BindGroupEntry {
+                    binding:  1,
+                    resource: textures,
+                },
+                BindGroupEntry {
+                    binding:  2,
+                    resource: BindingResource::Sampler(renderer.get_sampler(sampler)),
+                },
+                BindGroupEntry {
+                    binding:  3,
+                    resource: BindingResource::TextureView(&palette_view),
+                },
+                BindGroupEntry {
+                    binding:  4,
+                    resource: BindingResource::Sampler(
+                        renderer.get_sampler(SamplerConfig::NEAREST),
+                    ),
+                },
+            ],
+            label:   None,
+        })
+    }
+
+    pub fn set_palette_texture(&mut self, renderer: &Renderer, palette_texture: u64) {
+        self.palette_texture = palette_texture;
+        let textures = self.texture_layers.keys().copied().collect();
+        self.update_texture_groups(renderer, textures);
+    }
+
+    pub fn update_texture_groups(&mut self, renderer: &Renderer, textures: Vec<SpriteTextureKey>) {
+        for group in self.texture_groups.drain(..) {
+            if let Some(array_texture) = group.array_texture {
+                renderer.unload_texture(array_texture);
+            }
+        }
+        self.texture_layers.clear();
+
+        // (texture, sampler) pairs with the sprite sampler falling back to the texture sampler
+        let resolved = textures
+            .into_iter()
+            .map(|key| {
+                let info = renderer.get_texture_info(key.0);
+                (key, info, key.1.unwrap_or(info.sampler))
+            })
+            .collect::<Vec<_>>();
+
+        let groups: Vec<(SamplerConfig, Vec<SpriteTextureKey>)> = match self.mode {
+            SpriteTextureMode::TextureArray => resolved
+                .into_iter()
+                .sorted_by_key(|(key, info, sampler)| {
+                    let size = info.size;
+                    (
+                        size.width,
+                        size.height,
+                        info.mip_level_count,
+                        info.format as u32,
+                        *sampler,
+                        key.0,
+                    )
+                })
+                .group_by(|(_, info, sampler)| {
+                    (info.size, info.mip_level_count, info.format, *sampler)
+                })
+                .into_iter()
+                .flat_map(|((.., sampler), group)| {
+                    let keys = group.map(|(key, ..)| key).collect::<Vec<_>>();
+                    Self::chunk_by_texture(keys, MAX_TEXTURE_ARRAY_LAYERS)
+                        .into_iter()
+                        .map(move |chunk| (sampler, chunk))
+                })
+                .collect(),
+            SpriteTextureMode::BindingArray => resolved
+                .into_iter()
+                .sorted_by_key(|(key, _, sampler)| (*sampler, key.0))
+                .group_by(|(_, _, sampler)| *sampler)
+                .into_iter()
+                .flat_map(|(sampler, group)| {
+                    let keys = group.map(|(key, ..)| key).collect::<Vec<_>>();
+                    Self::chunk_by_texture(keys, MAX_BINDING_ARRAY_TEXTURES)
+                        .into_iter()
+                        .map(move |chunk| (sampler, chunk))
+                })
+                .collect(),
+        };
+
+        for (index, (sampler, keys)) in groups.into_iter().enumerate() {
+            let textures = keys.iter().map(|key| key.0).unique().collect::<Vec<_>>();
+            for key in keys {
+                let layer = textures.iter().position(|id| *id == key.0).unwrap();
+                self.texture_layers.insert(key, (index, layer as u32));
+            }
+
+            let (array_texture, bind_group) = match self.mode {
+                SpriteTextureMode::TextureArray => {
+                    let array_texture = if textures.len() > 1 {
+                        Some(*renderer.load_texture_array(&textures).key())
+                    } else {
+                        None
+                    };
+                    let texture = renderer.get_texture(array_texture.unwrap_or(textures[0]));
+                    let texture_view = texture.create_view(&TextureViewDescriptor {
+                        dimension: Some(TextureViewDimension::D2Array),
+                        ..TextureViewDescriptor::default()
+                    });
+                    (
+                        array_texture,
+                        self.create_bind_group(
+                            renderer,
+                            BindingResource::TextureView(&texture_view),
+                            sampler,
+                        ),
+                    )
+                }
+                SpriteTextureMode::BindingArray => {
+                    let texture_views = textures
+                        .iter()
+                        .map(|id| {
+                            renderer
+                                .get_texture(*id)
+                                .create_view(&TextureViewDescriptor::default())
+                        })
+                        .collect::<Vec<TextureView>>();
+                    // unused slots repeat the last view, the layout requires a full array
+                    let texture_views = (0..MAX_BINDING_ARRAY_TEXTURES)
+                        .map(|i| &texture_views[i.min(texture_views.len() - 1)])
+                        .collect::<Vec<&TextureView>>();
+                    (
+                        None,
+                        self.create_bind_group(
+                            renderer,
+                            BindingResource::TextureViewArray(&texture_views),
+                            sampler,
+                        ),
+                    )
+                }
+            };
+
+            self.texture_groups.push(SpriteTextureGroup {
+                textures,
+                array_texture,
+                bind_group,
+            });
+        }
+    }
+
+    // splits keys into chunks of at most `size` distinct textures, keys are sorted by texture
+    fn chunk_by_texture(keys: Vec<SpriteTextureKey>, size: usize) -> Vec<Vec<SpriteTextureKey>> {
+        let mut chunks: Vec<Vec<SpriteTextureKey>> = Vec::new();
+        let mut count = 0;
+        for key in keys {
+            let new_texture = chunks
+                .last()
+                .and_then(|chunk| chunk.last())
+                .map_or(true, |last| last.0 != key.0);
+            if chunks.is_empty() || (new_texture && count == size) {
+                chunks.push(Vec::new());
+                count = 0;
+            }
+            if new_texture {
+                count += 1;
+            }
+            chunks.last_mut().unwrap().push(key);
+        }
+        chunks
+    }
+
+    fn create_render_pipeline(&self, renderer: &Renderer, blend: BlendMode) -> RenderPipeline {
+        renderer
+            .device
+            .create_render_pipeline(&RenderPipelineDescriptor {
+                layout:                    Some(&self.pipeline_layout),
+                vertex_stage:              ProgrammableStageDescriptor {
+                    module:      &self.vs_module,
+                    entry_point: "main",
+                },
+                fragment_stage:            Some(ProgrammableStageDescriptor {
+                    module:      &self.fs_module,
+                    entry_point: "main",
+                }),
+                rasterization_state:       Some(RasterizationStateDescriptor {
+                    front_face:             FrontFace::Ccw,
+                    cull_mode:              CullMode::None,
+                    depth_bias:             0,
+                    depth_bias_slope_scale: 0.0,
+                    depth_bias_clamp:       0.0,
+                    clamp_depth:            false,
+                    polygon_mode:           PolygonMode::Fill,
+                }),
+                primitive_topology:        PrimitiveTopology::TriangleList,
+                color_states:              &[ColorStateDescriptor {
+                    format:      renderer.format,
+                    color_blend: blend.color_blend(),
+                    alpha_blend: blend.alpha_blend(),
+                    write_mask:  ColorWrite::ALL,
+                }],
+                depth_stencil_state:       None,
+                sample_count:              renderer.sample_count,
+                alpha_to_coverage_enabled: false,
+                sample_mask:               !0,
+                vertex_state:              VertexStateDescriptor {
+                    index_format:   None,
+                    vertex_buffers: &[VertexBufferDescriptor {
+                        stride:     Self::STRIDE,
+                        step_mode:  InputStepMode::Instance,
+                        attributes: &[
+                            VertexAttributeDescriptor {
+                                offset:          0,
+                                shader_location: 0,
+                                format:          VertexFormat::Float4,
+                            },
+                            VertexAttributeDescriptor {
+                                offset:          16,
+                                shader_location: 1,
+                                format:          VertexFormat::Float4,
+                            },
+                            VertexAttributeDescriptor {
+                                offset:          32,
+                                shader_location: 2,
+                                format:          VertexFormat::Float4,
+                            },
+                            VertexAttributeDescriptor {
+                                offset:          48,
+                                shader_location: 3,
+                                format:          VertexFormat::Float2,
+                            },
+                            VertexAttributeDescriptor {
+                                offset:          56,
+                                shader_location: 4,
+                                format:          VertexFormat::Float2,
+                            },
+                            VertexAttributeDescriptor {
+                                offset:          64,
+                                shader_location: 5,
+                                format:          VertexFormat::Float4,
+                            },
+                            VertexAttributeDescriptor {
+                                offset:          80,
+                                shader_location: 6,
+                                format:          VertexFormat::Float,
+                            },
+                            VertexAttributeDescriptor {
+                                offset:          84,
+                                shader_location: 7,
+                                format:          VertexFormat::Uint,
+                            },
+                            VertexAttributeDescriptor {
+                                offset:          88,
+                                shader_location: 8,
+                                format:          VertexFormat::Uint,
+                            },
+                            VertexAttributeDescriptor {
+                                offset:          92,
+                                shader_location: 9,
+                                format:          VertexFormat::Uint,
+                            },
+                            VertexAttributeDescriptor {
+                                offset:          96,
+                                shader_location: 10,
+                                format:          VertexFormat::Float2,
+                            },
+                        ],
+                    }],
+                },
+                label:                     None,
+            })
+    }
+
+    pub fn prepare_blend_modes(&mut self, renderer: &Renderer) {
+        if self.sample_count != renderer.sample_count {
+            self.pipelines.clear();
+            self.sample_count = renderer.sample_count;
+        }
+        for (_, _, _, blend, _) in self.draw_instances.iter() {
+            #[allow(clippy::map_entry)]
+            if !self.pipelines.contains_key(blend) {
+                let pipeline = self.create_render_pipeline(renderer, *blend);
+                self.pipelines.insert(*blend, pipeline);
+            }
+        }
+    }
+
     pub fn prepare(
-        &mut self, renderer: &Renderer, data: (&View<Transform>, &View<Sprite>), camera: &Camera,
+        &mut self, renderer: &Renderer, data: (&View<GlobalTransform>, &View<Sprite>),
+        layers: &View<RenderLayers>, cameras: &[(EntityId, &Camera)],
     ) {
         let data_count = data.fast_iter().count() as u64;
         self.update_element_count(renderer, data_count);
+        self.update_camera_count(renderer, cameras.len() as u64);
 
         let vertex_buffer = renderer.get_buffer(self.vertex_buffer);
         let camera_buffer = renderer.get_buffer(self.camera_buffer);
 
-        renderer
-            .queue
-            .write_buffer(&camera_buffer, 0, bytes_of(&camera.data()));
+        let stride = get_buffer_size::<CameraData>() as usize;
+        let mut camera_data = vec![0; stride * cameras.len()];
+        self.cameras.clear();
+        for (index, (entity, camera)) in cameras.iter().enumerate() {
+            let offset = index * stride;
+            camera_data[offset..offset + size_of::<CameraData>()]
+                .copy_from_slice(bytes_of(&camera.data()));
+            self.cameras
+                .insert(Self::pass_name(*entity), (offset as u32, camera.layers));
+        }
+        if !camera_data.is_empty() {
+            renderer.write_buffer(&camera_buffer, 0, &camera_data);
+        }
 
         self.vertex_buffer_data.clear();
         self.draw_instances.clear();
 
-        data.fast_iter()
-            .sorted_by(|(_, a), (_, b)| a.texture.cmp(&b.texture))
-            .enumerate()
-            .for_each(|(i, (transform, sprite))| {
-                self.vertex_buffer_data
-                    .extend_from_slice(bytes_of(transform));
-                self.vertex_buffer_data
-                    .extend_from_slice(bytes_of(sprite.data()));
-
-                #[allow(clippy::map_entry)]
-                if !self.bind_groups.contains_key(&sprite.texture) {
-                    let texture = renderer.get_texture(sprite.texture);
-                    let texture_view = texture.create_view(&TextureViewDescriptor::default());
-                    let bind_group = renderer.device.create_bind_group(&BindGroupDescriptor {
-                        layout:  &self.bind_group_layout,
-                        entries: &[
-                            BindGroupEntry {
-                                binding:  0,
-                                resource: BindingResource::Buffer {
-                                    buffer: &camera_buffer,
-                                    offset: 0,
-                                    size:   BufferSize::new(size_of::<CameraData>() as u64),
-                                },
-                            },
-                            BindGroupEntry {
-                                binding:  1,
-                                resource: BindingResource::TextureView(&texture_view),
-                            },
-                            BindGroupEntry {
-                                binding:  2,
-                                resource: BindingResource::Sampler(&self.texture_sampler),
-                            },
-                        ],
-                        label:   None,
-                    });
-                    self.bind_groups.insert(sprite.texture, bind_group);
-                }
+        if data
+            .fast_iter()
+            .any(|(_, sprite)| !self.texture_layers.contains_key(&sprite.texture_key()))
+        {
+            let textures = self
+                .texture_layers
+                .keys()
+                .copied()
+                .chain(data.fast_iter().map(|(_, sprite)| sprite.texture_key()))
+                .unique()
+                .collect();
+            self.update_texture_groups(renderer, textures);
+        }
 
-                let len = self.draw_instances.len();
-                if len == 0 {
-                    self.draw_instances.push((0, 0, sprite.texture));
-                } else if self.draw_instances[len - 1].2 != sprite.texture {
-                    self.draw_instances[len - 1].1 = i as u32 - 1;
-                    self.draw_instances
-                        .push((i as u32, i as u32, sprite.texture));
-                }
+        // sprites on layers no camera draws are skipped
+        let visible = cameras
+            .iter()
+            .fold(RenderLayers::NONE, |visible, (_, camera)| {
+                visible | camera.layers
             });
-        let len = self.draw_instances.len();
-        self.draw_instances[len - 1].1 = data_count as u32 - 1;
-
-        renderer
-            .queue
-            .write_buffer(&vertex_buffer, 0, &self.vertex_buffer_data[..]);
-    }
-
-    pub fn draw<'s>(&'s mut self, renderer: &'s Renderer, pass: &mut RenderPass<'s>) {
-        pass.set_pipeline(&self.pipeline);
-        pass.set_vertex_buffer(0, (renderer.get_buffer(self.vertex_buffer)).slice(..));
+        let texture_layers = &self.texture_layers;
+        for (i, (transform, sprite, (group, layer), sprite_layers)) in data
+            .iter()
+            .with_id()
+            .map(|(id, (transform, sprite))| {
+                let sprite_layers = layers
+                    .get(id)
+                    .map_or(RenderLayers::DEFAULT, |layers| *layers);
+                (
+                    transform,
+                    sprite,
+                    texture_layers[&sprite.texture_key()],
+                    sprite_layers,
+                )
+            })
+            .filter(|(.., sprite_layers)| sprite_layers.intersects(visible))
+            .sorted_by_key(|(_, sprite, (group, _), sprite_layers)| {
+                (sprite.blend, *group, *sprite_layers)
+            })
+            .enumerate()
+        {
+            let mut sprite_data = SpriteData {
+                texture_layer: layer,
+                ..*sprite.data()
+            };
+            if sprite.blend.premultiply() {
+                sprite_data.flags |= SpriteData::PREMULTIPLY;
+            }
+            self.vertex_buffer_data
+                .extend_from_slice(bytes_of(&transform.0));
+            self.vertex_buffer_data
+                .extend_from_slice(bytes_of(&sprite_data));
+
+            match self.draw_instances.last_mut() {
+                Some((_, end, last, blend, last_layers))
+                    if *last == group
+                        && *blend == sprite.blend
+                        && *last_layers == sprite_layers =>
+                {
+                    *end = i as u32 + 1
+                }
+                _ => self.draw_instances.push((
+                    i as u32,
+                    i as u32 + 1,
+                    group,
+                    sprite.blend,
+                    sprite_layers,
+                )),
+            }
+        }
+        self.prepare_blend_modes(renderer);
 
-        for (start, end, bind_group) in self.draw_instances.iter() {
-            pass.set_bind_group(0, &self.bind_groups[&bind_group], &[]);
-            pass.draw(0..6, *start..*end as u32);
+        if !self.vertex_buffer_data.is_empty() {
+            renderer.write_buffer(&vertex_buffer, 0, &self.vertex_buffer_data[..]);
         }
     }
 }
 impl Pipeline for SpritePipeline {
+    fn requirements() -> Requirements {
+        Requirements {
+            optional_features: SpriteTextureMode::required_features(),
+            optional_limits: SpriteTextureMode::required_limits(),
+            ..Requirements::default()
+        }
+    }
+
     fn new(renderer: &Renderer) -> Self {
+        let mode = SpriteTextureMode::select(renderer.device.features(), &renderer.device.limits());
+
         let bind_group_layout =
             renderer
                 .device
@@ -154,8 +664,8 @@ impl Pipeline for SpritePipeline {
                             visibility: wgpu::ShaderStage::VERTEX,
                             ty:         wgpu::BindingType::Buffer {
                                 ty:                 wgpu::BufferBindingType::Uniform,
-                                has_dynamic_offset: false,
-                                min_binding_size:   None,
+                                has_dynamic_offset: true,
+                                min_binding_size:   BufferSize::new(size_of::<CameraData>() as u64),
                             },
                             count:      None,
                         },
@@ -164,10 +674,20 @@ impl Pipeline for SpritePipeline {
                             visibility: wgpu::ShaderStage::FRAGMENT,
                             ty:         wgpu::BindingType::Texture {
                                 multisampled:   false,
-                                view_dimension: wgpu::TextureViewDimension::D2,
+                                view_dimension: match mode {
+                                    SpriteTextureMode::TextureArray => {
+                                        TextureViewDimension::D2Array
+                                    }
+                                    SpriteTextureMode::BindingArray => TextureViewDimension::D2,
+                                },
                                 sample_type:    wgpu::TextureSampleType::Float { filterable: true },
                             },
-                            count:      None,
+                            count:      match mode {
+                                SpriteTextureMode::TextureArray => None,
+                                SpriteTextureMode::BindingArray => {
+                                    NonZeroU32::new(MAX_BINDING_ARRAY_TEXTURES as u32)
+                                }
+                            },
                         },
                         wgpu::BindGroupLayoutEntry {
                             binding:    2,
@@ -178,6 +698,25 @@ impl Pipeline for SpritePipeline {
                             },
                             count:      None,
                         },
+                        wgpu::BindGroupLayoutEntry {
+                            binding:    3,
+                            visibility: wgpu::ShaderStage::FRAGMENT,
+                            ty:         wgpu::BindingType::Texture {
+                                multisampled:   false,
+                                view_dimension: TextureViewDimension::D2,
+                                sample_type:    wgpu::TextureSampleType::Float { filterable: true },
+                            },
+                            count:      None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding:    4,
+                            visibility: wgpu::ShaderStage::FRAGMENT,
+                            ty:         wgpu::BindingType::Sampler {
+                                comparison: false,
+                                filtering:  true,
+                            },
+                            count:      None,
+                        },
                     ],
                     label:   None,
                 });
@@ -188,23 +727,17 @@ impl Pipeline for SpritePipeline {
             BufferUsage::VERTEX | BufferUsage::COPY_DST,
         );
 
-        let camera_buffer =
-            renderer.load_buffer::<CameraData>(1, BufferUsage::UNIFORM | BufferUsage::COPY_DST);
-
-        let texture_sampler = renderer.device.create_sampler(&SamplerDescriptor {
-            address_mode_u:   AddressMode::ClampToEdge,
-            address_mode_v:   AddressMode::ClampToEdge,
-            address_mode_w:   AddressMode::ClampToEdge,
-            mag_filter:       FilterMode::Nearest,
-            min_filter:       FilterMode::Linear,
-            mipmap_filter:    FilterMode::Nearest,
-            lod_min_clamp:    -100.0,
-            lod_max_clamp:    100.0,
-            compare:          None,
-            anisotropy_clamp: None,
-            border_color:     None,
-            label:            None,
-        });
+        let camera_capacity = 1;
+        let camera_buffer = renderer.load_buffer::<CameraData>(
+            camera_capacity,
+            BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+        );
+
+        let palette_texture = renderer.load_texture_image(
+            "default palette",
+            RgbaImage::from_pixel(1, 1, Rgba([255, 255, 255, 255])),
+            TextureConfig::default(),
+        );
 
         let pipeline_layout = renderer
             .device
@@ -221,107 +754,67 @@ impl Pipeline for SpritePipeline {
                 label:  None,
                 flags:  wgpu::ShaderFlags::empty(),
             });
+        let fs_shader = match mode {
+            SpriteTextureMode::TextureArray => "sprite/simple.frag",
+            SpriteTextureMode::BindingArray => "sprite/bindless.frag",
+        };
         let fs_module = renderer
             .device
             .create_shader_module(&ShaderModuleDescriptor {
-                source: wgpu::ShaderSource::SpirV(get_shader("sprite/simple.frag").unwrap().into()),
+                source: wgpu::ShaderSource::SpirV(get_shader(fs_shader).unwrap().into()),
                 label:  None,
                 flags:  wgpu::ShaderFlags::empty(),
             });
 
-        let pipeline = renderer
-            .device
-            .create_render_pipeline(&RenderPipelineDescriptor {
-                layout:                    Some(&pipeline_layout),
-                vertex_stage:              ProgrammableStageDescriptor {
-                    module:      &vs_module,
-                    entry_point: "main",
-                },
-                fragment_stage:            Some(ProgrammableStageDescriptor {
-                    module:      &fs_module,
-                    entry_point: "main",
-                }),
-                rasterization_state:       Some(RasterizationStateDescriptor {
-                    front_face:             FrontFace::Ccw,
-                    cull_mode:              CullMode::None,
-                    depth_bias:             0,
-                    depth_bias_slope_scale: 0.0,
-                    depth_bias_clamp:       0.0,
-                    clamp_depth:            false,
-                    polygon_mode:           PolygonMode::Fill,
-                }),
-                primitive_topology:        PrimitiveTopology::TriangleList,
-                color_states:              &[ColorStateDescriptor {
-                    format:      TextureFormat::Bgra8Unorm,
-                    color_blend: BlendDescriptor {
-                        src_factor: BlendFactor::SrcAlpha,
-                        dst_factor: BlendFactor::OneMinusSrcAlpha,
-                        operation:  BlendOperation::Add,
-                    },
-                    alpha_blend: BlendDescriptor {
-                        src_factor: BlendFactor::One,
-                        dst_factor: BlendFactor::One,
-                        operation:  BlendOperation::Add,
-                    },
-                    write_mask:  ColorWrite::ALL,
-                }],
-                depth_stencil_state:       None,
-                sample_count:              1,
-                alpha_to_coverage_enabled: false,
-                sample_mask:               0,
-                vertex_state:              VertexStateDescriptor {
-                    index_format:   None,
-                    vertex_buffers: &[VertexBufferDescriptor {
-                        stride:     Self::STRIDE,
-                        step_mode:  InputStepMode::Instance,
-                        attributes: &[
-                            VertexAttributeDescriptor {
-                                offset:          0,
-                                shader_location: 0,
-                                format:          VertexFormat::Float4,
-                            },
-                            VertexAttributeDescriptor {
-                                offset:          16,
-                                shader_location: 1,
-                                format:          VertexFormat::Float4,
-                            },
-                            VertexAttributeDescriptor {
-                                offset:          32,
-                                shader_location: 2,
-                                format:          VertexFormat::Float4,
-                            },
-                            VertexAttributeDescriptor {
-                                offset:          48,
-                                shader_location: 3,
-                                format:          VertexFormat::Float2,
-                            },
-                            VertexAttributeDescriptor {
-                                offset:          56,
-                                shader_location: 4,
-                                format:          VertexFormat::Float2,
-                            },
-                        ],
-                    }],
-                },
-                label:                     None,
-            });
-
         Self {
+            mode,
+
             bind_group_layout,
 
-            bind_groups: HashMap::new(),
+            texture_layers: HashMap::new(),
+            texture_groups: Vec::new(),
 
-            pipeline,
+            pipeline_layout,
+            vs_module,
+            fs_module,
+            pipelines: HashMap::new(),
+            sample_count: renderer.sample_count,
 
             vertex_buffer: *vertex_buffer.key(),
             vertex_buffer_data: Vec::new(),
             vertex_buffer_size,
 
             camera_buffer: *camera_buffer.key(),
+            camera_capacity,
+            cameras: HashMap::new(),
+            configured: None,
 
-            texture_sampler,
+            palette_texture: *palette_texture.key(),
 
             draw_instances: Vec::new(),
         }
     }
+
+    fn draw<'s>(&'s mut self, renderer: &'s Renderer, pass: &mut RenderPass<'s>, name: &str) {
+        let (camera_offset, camera_layers) = match self.cameras.get(name) {
+            Some(camera) => *camera,
+            None => return,
+        };
+        pass.set_vertex_buffer(0, (renderer.get_buffer(self.vertex_buffer)).slice(..));
+
+        let mut current_blend = None;
+        for (start, end, group, blend, _) in self
+            .draw_instances
+            .iter()
+            .filter(|(.., layers)| layers.intersects(camera_layers))
+        {
+            if current_blend != Some(*blend) {
+                pass.set_pipeline(&self.pipelines[blend]);
+                current_blend = Some(*blend);
+            }
+            pass.set_bind_group(0, &self.texture_groups[*group].bind_group, &[camera_offset]);
+            pass.draw(0..6, *start..*end);
+            renderer.stats.add_draw_call();
+        }
+    }
 }
@@ -1,20 +1,45 @@
 use std::{
-    any::TypeId,
+    any::{type_name, TypeId},
     intrinsics::transmute,
     mem::size_of,
-    sync::atomic::{AtomicU64, Ordering},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
 };
 
 use dashmap::{
     mapref::one::{Ref, RefMut},
     DashMap,
 };
+use futures::executor::block_on;
+use image::{
+    imageops::{self, FilterType},
+    RgbaImage,
+};
+use serde::{Deserialize, Serialize};
 use wgpu::{
-    Buffer, BufferDescriptor, BufferUsage, Device, PresentMode, Queue, Surface, SwapChain,
-    SwapChainDescriptor, SwapChainError, SwapChainFrame, Texture, TextureFormat, TextureUsage,
+    Adapter, Buffer, BufferDescriptor, BufferUsage, Color, CommandEncoderDescriptor, Device,
+    ErrorFilter, Extent3d, Features, Limits, LoadOp, Operations, Origin3d, Queue,
+    RenderPassColorAttachmentDescriptor, RenderPassDescriptor, Sampler, Surface, SwapChain,
+    SwapChainDescriptor, SwapChainError, SwapChainFrame, Texture, TextureCopyView,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureUsage, TextureView,
+    TextureViewDescriptor,
 };
 
-use crate::{graphics::pipelines, resources::get_image};
+use crate::{
+    graphics::{
+        capture::{save_screenshot, Readback, RecordConfig, Recorder},
+        errors::{ErrorContext, ErrorLog},
+        pipelines,
+        stats::{FrameStats, GpuTimer},
+        texture::{
+            get_mip_level_count, get_srgb_format, SamplerConfig, TextureConfig, TextureInfo,
+        },
+    },
+    resources::{get_image, get_texture_config},
+};
 use pipelines::Pipeline;
 
 
@@ -23,12 +48,35 @@ pub fn get_aligned<T: Sized>(alignment: u64) -> u64 {
 }
 pub fn get_buffer_size<T: Sized>() -> u64 { get_aligned::<T>(wgpu::BIND_BUFFER_ALIGNMENT) }
 
+// counts that can be requested, the adapter may support only some of them
+pub const SAMPLE_COUNTS: [u32; 4] = [1, 2, 4, 8];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PresentMode {
+    Vsync,
+    Mailbox,
+    Immediate,
+}
+impl From<PresentMode> for wgpu::PresentMode {
+    fn from(mode: PresentMode) -> Self {
+        match mode {
+            PresentMode::Vsync => wgpu::PresentMode::Fifo,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+        }
+    }
+}
+
 pub struct Resources {
     pub textures:  DashMap<u64, Texture>,
     pub buffers:   DashMap<u64, Buffer>,
     pub pipelines: DashMap<TypeId, Box<dyn Pipeline>>,
+    pub samplers:  DashMap<SamplerConfig, Sampler>,
+
+    pub pipeline_names: DashMap<TypeId, &'static str>,
 
     pub texture_cache: DashMap<String, u64>,
+    pub texture_info:  DashMap<u64, TextureInfo>,
 
     pub buffer_counter:  AtomicU64,
     pub texture_counter: AtomicU64,
@@ -39,8 +87,12 @@ impl Resources {
             textures:  DashMap::new(),
             buffers:   DashMap::new(),
             pipelines: DashMap::new(),
+            samplers:  DashMap::new(),
+
+            pipeline_names: DashMap::new(),
 
             texture_cache: DashMap::new(),
+            texture_info:  DashMap::new(),
 
             buffer_counter:  0.into(),
             texture_counter: 0.into(),
@@ -48,12 +100,45 @@ impl Resources {
     }
 }
 
+pub enum Frame {
+    SwapChain(SwapChainFrame),
+    Offscreen(TextureView),
+}
+impl Frame {
+    pub fn view(&self) -> &TextureView {
+        match self {
+            Frame::SwapChain(frame) => &frame.output.view,
+            Frame::Offscreen(view) => view,
+        }
+    }
+}
+
 pub struct Renderer {
-    pub device:    Device,
-    pub queue:     Queue,
-    pub surface:   Surface,
-    pub swapchain: Option<SwapChain>,
-    pub frame:     Option<SwapChainFrame>,
+    pub device:       Device,
+    pub queue:        Queue,
+    // headless renderers without a surface render into the offscreen texture
+    pub surface:      Option<Surface>,
+    pub swapchain:    Option<SwapChain>,
+    pub offscreen:    Option<Texture>,
+    pub frame:        Option<Frame>,
+    // requested mode, wgpu silently uses vsync when the surface doesn't support it
+    pub present_mode: PresentMode,
+    pub format:       TextureFormat,
+    // color textures and the swapchain are srgb so blending happens in linear space
+    pub srgb:         bool,
+
+    // color target rendered into and resolved to the frame when multisampling
+    pub sample_count: u32,
+    pub msaa_target:  Option<(Texture, TextureView)>,
+
+    pub errors: Arc<ErrorLog>,
+    pub stats:  FrameStats,
+    pub timer:  Option<GpuTimer>,
+
+    // the next frame is copied and saved as png into the screenshot directory
+    pub screenshot_path:      PathBuf,
+    pub screenshot_requested: AtomicBool,
+    pub recorder:             Option<Recorder>,
 
     resources: Resources,
 
@@ -62,55 +147,368 @@ pub struct Renderer {
 }
 
 impl Renderer {
-    pub fn new(device: Device, queue: Queue, surface: Surface) -> Self {
+    // features and limits to create the device with, required by or optional for the pipelines
+    pub fn negotiate(adapter: &Adapter) -> Result<(Features, Limits), String> {
+        let requirements = pipelines::requirements().combine(GpuTimer::requirements());
+        let (features, limits) = (adapter.features(), adapter.limits());
+        let missing = requirements.features - features;
+        if !missing.is_empty() {
+            return Err(format!(
+                "adapter is missing required features {:?}",
+                missing
+            ));
+        }
+        if !pipelines::limits_within(&requirements.limits, &limits) {
+            return Err(format!(
+                "adapter limits {:?} are below the required {:?}",
+                limits, requirements.limits
+            ));
+        }
+        let optional_limits =
+            pipelines::map_limits(&requirements.optional_limits, &limits, u32::min);
+        Ok((
+            requirements.features | (requirements.optional_features & features),
+            pipelines::map_limits(&requirements.limits, &optional_limits, u32::max),
+        ))
+    }
+
+    pub fn new(
+        device: Device, queue: Queue, surface: Option<Surface>, format: TextureFormat, srgb: bool,
+        errors: ErrorLog,
+    ) -> Self {
+        let errors = Arc::new(errors);
+        let handler_errors = errors.clone();
+        device.on_uncaptured_error(move |error| {
+            handler_errors.record(&error, handler_errors.current_context(), false);
+        });
         Self {
             device,
             queue,
             surface,
             swapchain: None,
+            offscreen: None,
             frame: None,
+            present_mode: PresentMode::Mailbox,
+            format: get_srgb_format(format, srgb),
+            srgb,
+            sample_count: 1,
+            msaa_target: None,
+            errors,
+            stats: FrameStats::default(),
+            timer: None,
+            screenshot_path: PathBuf::from("./screenshots"),
+            screenshot_requested: false.into(),
+            recorder: None,
             resources: Resources::new(),
             width: 1,
             height: 1,
         }
     }
 
-    pub fn swap(&mut self) -> Result<(), SwapChainError> {
-        if self.swapchain.is_some() {
-            self.frame = Some(self.swapchain.as_ref().unwrap().get_current_frame()?);
+    // outdated, lost and out of memory swapchains are recreated and timed out frames skipped,
+    // leaving no frame to render to
+    pub fn swap(&mut self) {
+        self.stats.reset();
+        if self.surface.is_none() {
+            self.frame = self.offscreen.as_ref().map(|texture| {
+                Frame::Offscreen(texture.create_view(&TextureViewDescriptor::default()))
+            });
+            return;
+        }
+        if self.swapchain.is_none() {
+            return;
+        }
+        match self.swapchain.as_ref().unwrap().get_current_frame() {
+            Ok(frame) => {
+                if frame.suboptimal {
+                    tracing::debug!("swapchain frame is suboptimal");
+                }
+                self.frame = Some(Frame::SwapChain(frame));
+            }
+            Err(SwapChainError::Timeout) => {
+                tracing::warn!("swapchain frame timed out, skipping frame");
+                self.frame = None;
+            }
+            Err(error @ SwapChainError::Outdated) | Err(error @ SwapChainError::Lost) => {
+                tracing::warn!("swapchain {:?}, recreating", error);
+                self.frame = None;
+                self.create_swap_chain(self.width, self.height);
+            }
+            Err(SwapChainError::OutOfMemory) => {
+                tracing::error!("swapchain out of memory, recreating and skipping frame");
+                self.frame = None;
+                self.create_swap_chain(self.width, self.height);
+            }
+        }
+    }
+
+    pub fn present(&mut self) {
+        self.frame = None;
+        self.errors.next_frame();
+    }
+
+    // gpu times are only measured when enabled as reading them stalls the frame
+    pub fn enable_gpu_timer(&mut self) -> bool {
+        if self.timer.is_none() {
+            self.timer = GpuTimer::new(&self.device, &self.queue);
+        }
+        self.timer.is_some()
+    }
+
+    pub fn request_screenshot(&self) { self.screenshot_requested.store(true, Ordering::Release); }
+
+    pub fn take_screenshot_request(&self) -> bool {
+        self.frame.is_some() && self.screenshot_requested.swap(false, Ordering::AcqRel)
+    }
+
+    // swapchain textures can't be copied from, so captured frames render into a separate
+    // target that is drawn to the frame afterwards
+    pub fn create_capture_target(&self) -> (Texture, TextureView) {
+        let texture = self.device.create_texture(&TextureDescriptor {
+            size:            Extent3d {
+                width:  self.width,
+                height: self.height,
+                depth:  1,
+            },
+            mip_level_count: 1,
+            sample_count:    1,
+            dimension:       TextureDimension::D2,
+            format:          self.format,
+            usage:           TextureUsage::RENDER_ATTACHMENT
+                | TextureUsage::SAMPLED
+                | TextureUsage::COPY_SRC,
+            label:           Some("capture target"),
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        (texture, view)
+    }
+
+    pub fn save_screenshot(&self, readback: Readback) {
+        let image = match readback.read(&self.device) {
+            Some(image) => image,
+            None => {
+                tracing::error!("failed to read back the screenshot");
+                return;
+            }
+        };
+        match save_screenshot(&image, &self.screenshot_path) {
+            Ok(path) => println!("screenshot saved to {:?}", path),
+            Err(e) => tracing::error!("failed to save screenshot: {}", e),
         }
-        Ok(())
     }
 
-    pub fn present(&mut self) { self.frame = None; }
+    pub fn start_recording(&mut self, config: &RecordConfig) -> bool {
+        self.stop_recording();
+        match Recorder::new(config) {
+            Ok(recorder) => {
+                println!("recording to {:?}", config.path);
+                self.recorder = Some(recorder);
+                true
+            }
+            Err(e) => {
+                tracing::error!("failed to start recording: {}", e);
+                false
+            }
+        }
+    }
+
+    pub fn stop_recording(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            let captured = recorder.captured();
+            recorder.finish(&self.device);
+            println!("recorded {} frames", captured);
+        }
+    }
+
+    pub fn write_buffer(&self, buffer: &Buffer, offset: u64, data: &[u8]) {
+        self.stats.add_upload(data.len() as u64);
+        self.queue.write_buffer(buffer, offset, data);
+    }
+
+    pub fn push_error_scope(&self, context: ErrorContext) {
+        self.errors.push_context(context);
+        self.device.push_error_scope(ErrorFilter::Validation);
+        self.device.push_error_scope(ErrorFilter::OutOfMemory);
+    }
+
+    pub fn pop_error_scope(&self) {
+        let context = self.errors.pop_context().unwrap_or_default();
+        let out_of_memory = block_on(self.device.pop_error_scope());
+        let validation = block_on(self.device.pop_error_scope());
+        for error in out_of_memory.iter().chain(validation.iter()) {
+            self.errors.record(error, context.clone(), true);
+        }
+    }
+
+    pub fn error_scope<R>(&self, context: ErrorContext, scope: impl FnOnce() -> R) -> R {
+        self.push_error_scope(context);
+        let result = scope();
+        self.pop_error_scope();
+        result
+    }
 
     pub fn create_swap_chain(&mut self, width: u32, height: u32) {
         if width == 0 || height == 0 {
             self.swapchain = None;
+            self.offscreen = None;
+            self.msaa_target = None;
             return;
         }
+        // the frame has to be dropped before its swapchain
+        self.frame = None;
+        self.swapchain = None;
+        let surface = match self.surface.as_ref() {
+            Some(surface) => surface,
+            None => {
+                self.create_offscreen_target(width, height);
+                return;
+            }
+        };
         self.swapchain = Some(
             self.device
-                .create_swap_chain(&self.surface, &SwapChainDescriptor {
+                .create_swap_chain(surface, &SwapChainDescriptor {
                     usage: TextureUsage::RENDER_ATTACHMENT,
-                    format: TextureFormat::Bgra8Unorm,
+                    format: self.format,
                     width,
                     height,
-                    present_mode: PresentMode::Mailbox,
+                    present_mode: self.present_mode.into(),
                 }),
         );
-        println!("swapchain created with w: {} h: {}", width, height);
+        println!(
+            "swapchain created with w: {} h: {} mode: {:?} (may be vsync if unsupported)",
+            width, height, self.present_mode
+        );
+        self.width = width;
+        self.height = height;
+        self.msaa_target = self.create_msaa_target();
+    }
+
+    fn create_offscreen_target(&mut self, width: u32, height: u32) {
+        self.offscreen = Some(self.device.create_texture(&TextureDescriptor {
+            size:            Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+            mip_level_count: 1,
+            sample_count:    1,
+            dimension:       TextureDimension::D2,
+            format:          self.format,
+            usage:           TextureUsage::RENDER_ATTACHMENT | TextureUsage::COPY_SRC,
+            label:           Some("offscreen target"),
+        }));
         self.width = width;
         self.height = height;
+        self.msaa_target = self.create_msaa_target();
+    }
+
+    pub fn set_present_mode(&mut self, present_mode: PresentMode) {
+        if present_mode != self.present_mode {
+            self.present_mode = present_mode;
+            if self.swapchain.is_some() {
+                self.create_swap_chain(self.width, self.height);
+            }
+        }
+    }
+
+    fn create_msaa_target(&self) -> Option<(Texture, TextureView)> {
+        if self.sample_count <= 1 || (self.swapchain.is_none() && self.offscreen.is_none()) {
+            return None;
+        }
+        let texture = self.create_msaa_texture(self.width, self.height, self.sample_count);
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        Some((texture, view))
+    }
+
+    fn create_msaa_texture(&self, width: u32, height: u32, sample_count: u32) -> Texture {
+        self.device.create_texture(&TextureDescriptor {
+            size: Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: TextureDimension::D2,
+            format: self.format,
+            usage: TextureUsage::RENDER_ATTACHMENT,
+            label: Some("msaa target"),
+        })
+    }
+
+    // textures and pipelines accept any of the counts, only beginning a render pass checks them
+    // against the adapter, so each count is probed with a pass clearing a small target
+    pub fn supported_sample_counts(&self) -> Vec<u32> {
+        SAMPLE_COUNTS
+            .iter()
+            .copied()
+            .filter(|sample_count| {
+                if *sample_count == 1 {
+                    return true;
+                }
+                self.device.push_error_scope(ErrorFilter::Validation);
+                let view = self
+                    .create_msaa_texture(1, 1, *sample_count)
+                    .create_view(&TextureViewDescriptor::default());
+                let mut encoder = self
+                    .device
+                    .create_command_encoder(&CommandEncoderDescriptor {
+                        label: Some("sample count probe"),
+                    });
+                encoder.begin_render_pass(&RenderPassDescriptor {
+                    color_attachments:        &[RenderPassColorAttachmentDescriptor {
+                        attachment:     &view,
+                        resolve_target: None,
+                        ops:            Operations {
+                            load:  LoadOp::Clear(Color::BLACK),
+                            store: false,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                    label:                    None,
+                });
+                encoder.finish();
+                block_on(self.device.pop_error_scope()).is_none()
+            })
+            .collect()
+    }
+
+    // falls back to the highest supported sample count below the requested one
+    pub fn set_sample_count(&mut self, sample_count: u32) -> u32 {
+        let supported = self.supported_sample_counts();
+        let selected = supported
+            .iter()
+            .copied()
+            .filter(|count| *count <= sample_count)
+            .max()
+            .unwrap_or(1);
+        if selected != sample_count {
+            tracing::warn!(
+                "sample count {} is not supported, using {} (supported: {:?})",
+                sample_count,
+                selected,
+                supported
+            );
+        }
+        if selected != self.sample_count {
+            self.sample_count = selected;
+            self.msaa_target = self.create_msaa_target();
+        }
+        selected
     }
 
     pub fn get_pipeline_mut<'s, P: Pipeline + 'static>(
         &'s self,
     ) -> RefMut<'s, TypeId, Box<dyn Pipeline>> {
         if !self.resources.pipelines.contains_key(&TypeId::of::<P>()) {
+            let context = ErrorContext::new("pipeline creation").pipeline(type_name::<P>());
+            let pipeline = self.error_scope(context, || P::new(&self));
+            self.resources
+                .pipeline_names
+                .insert(TypeId::of::<P>(), type_name::<P>());
             self.resources
                 .pipelines
-                .insert(TypeId::of::<P>(), Box::new(P::new(&self)));
+                .insert(TypeId::of::<P>(), Box::new(pipeline));
         }
         self.resources
             .pipelines
@@ -118,6 +516,24 @@ impl Renderer {
             .unwrap()
     }
 
+    pub fn get_pipeline_name(&self, id: TypeId) -> &'static str {
+        self.resources
+            .pipeline_names
+            .get(&id)
+            .map_or("unknown", |name| *name.value())
+    }
+
+    pub fn take_pipeline(&self, id: TypeId) -> Option<Box<dyn Pipeline>> {
+        self.resources
+            .pipelines
+            .remove(&id)
+            .map(|(_, pipeline)| pipeline)
+    }
+
+    pub fn return_pipeline(&self, id: TypeId, pipeline: Box<dyn Pipeline>) {
+        self.resources.pipelines.insert(id, pipeline);
+    }
+
     pub fn get_buffer<'a>(&'a self, id: u64) -> &'a Buffer {
         unsafe {
             transmute::<&Buffer, &'a Buffer>(self.resources.buffers.get(&id).unwrap().value())
@@ -158,7 +574,37 @@ impl Renderer {
         }
     }
 
+    pub fn get_texture_info(&self, id: u64) -> TextureInfo {
+        *self.resources.texture_info.get(&id).unwrap().value()
+    }
+
+    // the image path a texture was loaded from
+    pub fn get_texture_path(&self, id: u64) -> Option<String> {
+        self.resources
+            .texture_cache
+            .iter()
+            .find(|entry| *entry.value() == id)
+            .map(|entry| entry.key().clone())
+    }
+
+    pub fn get_sampler<'a>(&'a self, config: SamplerConfig) -> &'a Sampler {
+        if !self.resources.samplers.contains_key(&config) {
+            self.resources
+                .samplers
+                .insert(config, self.device.create_sampler(&config.descriptor()));
+        }
+        unsafe {
+            transmute::<&Sampler, &'a Sampler>(
+                self.resources.samplers.get(&config).unwrap().value(),
+            )
+        }
+    }
+
     pub fn load_texture(&self, path: &str) -> Ref<u64, Texture> {
+        self.load_texture_with_config(path, get_texture_config(path).unwrap_or_default())
+    }
+
+    pub fn load_texture_with_config(&self, path: &str, config: TextureConfig) -> Ref<u64, Texture> {
         let path = String::from(path);
         if self.resources.texture_cache.contains_key(&path) {
             let id = self.resources.texture_cache.get(&path).unwrap();
@@ -168,45 +614,149 @@ impl Renderer {
         }
 
         let image = get_image(&path).unwrap().to_rgba8();
+        let texture = self.load_texture_image(&path, image, config);
+        self.resources.texture_cache.insert(path, *texture.key());
+        texture
+    }
+
+    pub fn load_texture_image(
+        &self, label: &str, image: RgbaImage, config: TextureConfig,
+    ) -> Ref<u64, Texture> {
         let dimensions = image.dimensions();
+        let mip_level_count = if config.mipmaps {
+            get_mip_level_count(dimensions.0, dimensions.1)
+        } else {
+            1
+        };
+        let format = get_srgb_format(TextureFormat::Rgba8Unorm, self.srgb && !config.linear);
         let texture = self.device.create_texture(&wgpu::TextureDescriptor {
-            size:            wgpu::Extent3d {
+            size: wgpu::Extent3d {
                 width:  dimensions.0,
                 height: dimensions.1,
                 depth:  1,
             },
-            mip_level_count: 1,
-            sample_count:    1,
-            dimension:       wgpu::TextureDimension::D2,
-            format:          wgpu::TextureFormat::Rgba8Unorm,
-            usage:           wgpu::TextureUsage::all(),
-            label:           Some(&path),
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsage::all(),
+            label: Some(label),
         });
-        self.queue.write_texture(
-            wgpu::TextureCopyView {
-                texture:   &texture,
-                mip_level: 0,
-                origin:    wgpu::Origin3d::ZERO,
-            },
-            &image.into_raw(),
-            wgpu::TextureDataLayout {
-                offset:         0,
-                bytes_per_row:  4 * dimensions.0,
-                rows_per_image: dimensions.1,
-            },
-            wgpu::Extent3d {
+        for mip_level in 0..mip_level_count {
+            let width = (dimensions.0 >> mip_level).max(1);
+            let height = (dimensions.1 >> mip_level).max(1);
+            let level = if mip_level == 0 {
+                image.clone()
+            } else {
+                imageops::resize(&image, width, height, FilterType::Triangle)
+            };
+            self.queue.write_texture(
+                wgpu::TextureCopyView {
+                    texture: &texture,
+                    mip_level,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                &level.into_raw(),
+                wgpu::TextureDataLayout {
+                    offset:         0,
+                    bytes_per_row:  4 * width,
+                    rows_per_image: height,
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth: 1,
+                },
+            );
+        }
+
+        let id = self
+            .resources
+            .texture_counter
+            .fetch_add(1, Ordering::AcqRel);
+        self.resources.texture_info.insert(id, TextureInfo {
+            size: wgpu::Extent3d {
                 width:  dimensions.0,
                 height: dimensions.1,
                 depth:  1,
             },
-        );
+            mip_level_count,
+            sampler: config.sampler,
+            format,
+        });
+        self.resources.textures.insert(id, texture);
+        self.resources.textures.get(&id).unwrap()
+    }
+
+    pub fn load_texture_array(&self, layers: &[u64]) -> Ref<u64, Texture> {
+        let info = self.get_texture_info(layers[0]);
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            size:            Extent3d {
+                width:  info.size.width,
+                height: info.size.height,
+                depth:  layers.len() as u32,
+            },
+            mip_level_count: info.mip_level_count,
+            sample_count:    1,
+            dimension:       wgpu::TextureDimension::D2,
+            format:          info.format,
+            usage:           wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+            label:           Some(&format!("texture array {:?}", layers)),
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor { label: None });
+        for (layer, id) in layers.iter().enumerate() {
+            let layer_info = self.get_texture_info(*id);
+            debug_assert_eq!(layer_info.size, info.size);
+            debug_assert_eq!(layer_info.mip_level_count, info.mip_level_count);
+            debug_assert_eq!(layer_info.format, info.format);
+            for mip_level in 0..info.mip_level_count {
+                encoder.copy_texture_to_texture(
+                    TextureCopyView {
+                        texture: self.get_texture(*id),
+                        mip_level,
+                        origin: Origin3d::ZERO,
+                    },
+                    TextureCopyView {
+                        texture: &texture,
+                        mip_level,
+                        origin: Origin3d {
+                            x: 0,
+                            y: 0,
+                            z: layer as u32,
+                        },
+                    },
+                    Extent3d {
+                        width:  (info.size.width >> mip_level).max(1),
+                        height: (info.size.height >> mip_level).max(1),
+                        depth:  1,
+                    },
+                );
+            }
+        }
+        self.queue.submit(Some(encoder.finish()));
 
         let id = self
             .resources
             .texture_counter
             .fetch_add(1, Ordering::AcqRel);
-        self.resources.texture_cache.insert(path, id);
+        self.resources.texture_info.insert(id, TextureInfo {
+            size: Extent3d {
+                width:  info.size.width,
+                height: info.size.height,
+                depth:  layers.len() as u32,
+            },
+            ..info
+        });
         self.resources.textures.insert(id, texture);
         self.resources.textures.get(&id).unwrap()
     }
+
+    pub fn unload_texture(&self, id: u64) {
+        self.resources.textures.remove(&id).unwrap();
+        self.resources.texture_info.remove(&id);
+        self.resources.texture_cache.retain(|_, v| *v != id);
+    }
 }
@@ -0,0 +1,119 @@
+use std::{
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use futures::executor::block_on;
+use serde::{Deserialize, Serialize};
+use wgpu::{
+    Buffer, BufferDescriptor, BufferUsage, CommandEncoder, Device, Features, Maintain, MapMode,
+    QuerySet, QuerySetDescriptor, QueryType, Queue, QUERY_SIZE,
+};
+
+use crate::graphics::pipelines::Requirements;
+
+
+// counters for the current frame, reset when the next frame is acquired
+#[derive(Default)]
+pub struct FrameStats {
+    prepare_nanos: AtomicU64,
+    upload_bytes:  AtomicU64,
+    draw_calls:    AtomicU64,
+}
+impl FrameStats {
+    pub fn reset(&self) {
+        self.prepare_nanos.store(0, Ordering::Release);
+        self.upload_bytes.store(0, Ordering::Release);
+        self.draw_calls.store(0, Ordering::Release);
+    }
+
+    pub fn add_prepare_time(&self, time: Duration) {
+        self.prepare_nanos
+            .fetch_add(time.as_nanos() as u64, Ordering::AcqRel);
+    }
+
+    pub fn add_upload(&self, bytes: u64) { self.upload_bytes.fetch_add(bytes, Ordering::AcqRel); }
+
+    pub fn add_draw_call(&self) { self.draw_calls.fetch_add(1, Ordering::AcqRel); }
+
+    pub fn sample(&self) -> FrameSample {
+        FrameSample {
+            prepare_ms:   self.prepare_nanos.load(Ordering::Acquire) as f64 / 1_000_000.0,
+            upload_bytes: self.upload_bytes.load(Ordering::Acquire),
+            draw_calls:   self.draw_calls.load(Ordering::Acquire),
+            gpu_ms:       None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FrameSample {
+    pub prepare_ms:   f64,
+    pub upload_bytes: u64,
+    pub draw_calls:   u64,
+    pub gpu_ms:       Option<f64>,
+}
+
+// measures the gpu time between two timestamps written into the frame encoder
+pub struct GpuTimer {
+    query_set: QuerySet,
+    buffer:    Buffer,
+    period:    f32,
+    written:   AtomicBool,
+}
+impl GpuTimer {
+    pub fn requirements() -> Requirements {
+        Requirements {
+            optional_features: Features::TIMESTAMP_QUERY,
+            ..Requirements::default()
+        }
+    }
+
+    pub fn new(device: &Device, queue: &Queue) -> Option<Self> {
+        if !device.features().contains(Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+        Some(Self {
+            query_set: device.create_query_set(&QuerySetDescriptor {
+                ty:    QueryType::Timestamp,
+                count: 2,
+            }),
+            buffer:    device.create_buffer(&BufferDescriptor {
+                label:              Some("gpu timer"),
+                size:               2 * QUERY_SIZE as u64,
+                usage:              BufferUsage::COPY_DST | BufferUsage::MAP_READ,
+                mapped_at_creation: false,
+            }),
+            period:    queue.get_timestamp_period(),
+            written:   false.into(),
+        })
+    }
+
+    pub fn begin(&self, encoder: &mut CommandEncoder) {
+        encoder.write_timestamp(&self.query_set, 0);
+    }
+
+    pub fn end(&self, encoder: &mut CommandEncoder) {
+        encoder.write_timestamp(&self.query_set, 1);
+        encoder.resolve_query_set(&self.query_set, 0..2, &self.buffer, 0);
+        self.written.store(true, Ordering::Release);
+    }
+
+    // blocks until the last frame finished, the buffer has to be read before the next frame
+    pub fn read(&self, device: &Device) -> Option<f64> {
+        if !self.written.swap(false, Ordering::AcqRel) {
+            return None;
+        }
+        let slice = self.buffer.slice(..);
+        let mapping = slice.map_async(MapMode::Read);
+        device.poll(Maintain::Wait);
+        block_on(mapping).ok()?;
+        let ticks = {
+            let data = slice.get_mapped_range();
+            let timestamps: &[u64] = bytemuck::cast_slice(&data);
+            timestamps[1].wrapping_sub(timestamps[0])
+        };
+        self.buffer.unmap();
+        Some(ticks as f64 * self.period as f64 / 1_000_000.0)
+    }
+}
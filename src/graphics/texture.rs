@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+use wgpu::{AddressMode, Extent3d, FilterMode, SamplerDescriptor, TextureFormat};
+
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+impl From<Filter> for FilterMode {
+    fn from(filter: Filter) -> Self {
+        match filter {
+            Filter::Nearest => FilterMode::Nearest,
+            Filter::Linear => FilterMode::Linear,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Wrap {
+    Clamp,
+    Repeat,
+    Mirror,
+}
+impl From<Wrap> for AddressMode {
+    fn from(wrap: Wrap) -> Self {
+        match wrap {
+            Wrap::Clamp => AddressMode::ClampToEdge,
+            Wrap::Repeat => AddressMode::Repeat,
+            Wrap::Mirror => AddressMode::MirrorRepeat,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SamplerConfig {
+    pub mag_filter:    Filter,
+    pub min_filter:    Filter,
+    pub mipmap_filter: Filter,
+    pub wrap_u:        Wrap,
+    pub wrap_v:        Wrap,
+}
+impl SamplerConfig {
+    pub const LINEAR: Self = Self {
+        mag_filter:    Filter::Linear,
+        min_filter:    Filter::Linear,
+        mipmap_filter: Filter::Linear,
+        wrap_u:        Wrap::Clamp,
+        wrap_v:        Wrap::Clamp,
+    };
+    pub const NEAREST: Self = Self {
+        mag_filter:    Filter::Nearest,
+        min_filter:    Filter::Nearest,
+        mipmap_filter: Filter::Nearest,
+        wrap_u:        Wrap::Clamp,
+        wrap_v:        Wrap::Clamp,
+    };
+
+    pub fn repeat(self) -> Self {
+        Self {
+            wrap_u: Wrap::Repeat,
+            wrap_v: Wrap::Repeat,
+            ..self
+        }
+    }
+
+    pub fn descriptor(&self) -> SamplerDescriptor<'static> {
+        SamplerDescriptor {
+            address_mode_u:   self.wrap_u.into(),
+            address_mode_v:   self.wrap_v.into(),
+            address_mode_w:   AddressMode::ClampToEdge,
+            mag_filter:       self.mag_filter.into(),
+            min_filter:       self.min_filter.into(),
+            mipmap_filter:    self.mipmap_filter.into(),
+            lod_min_clamp:    -100.0,
+            lod_max_clamp:    100.0,
+            compare:          None,
+            anisotropy_clamp: None,
+            border_color:     None,
+            label:            None,
+        }
+    }
+}
+impl Default for SamplerConfig {
+    fn default() -> Self {
+        Self {
+            mag_filter:    Filter::Nearest,
+            min_filter:    Filter::Linear,
+            mipmap_filter: Filter::Nearest,
+            wrap_u:        Wrap::Clamp,
+            wrap_v:        Wrap::Clamp,
+        }
+    }
+}
+
+// declared when loading a texture or read from a sidecar file next to the image
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextureConfig {
+    pub sampler: SamplerConfig,
+    pub mipmaps: bool,
+    // data textures like color lookup tables are never decoded as srgb
+    pub linear:  bool,
+    // sprites using the texture get a random tint in generated scenes
+    pub recolor: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TextureInfo {
+    pub size:            Extent3d,
+    pub mip_level_count: u32,
+    pub sampler:         SamplerConfig,
+    pub format:          TextureFormat,
+}
+
+pub fn get_mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+pub fn get_srgb_format(format: TextureFormat, srgb: bool) -> TextureFormat {
+    match (format, srgb) {
+        (TextureFormat::Rgba8Unorm, true) => TextureFormat::Rgba8UnormSrgb,
+        (TextureFormat::Bgra8Unorm, true) => TextureFormat::Bgra8UnormSrgb,
+        (TextureFormat::Rgba8UnormSrgb, false) => TextureFormat::Rgba8Unorm,
+        (TextureFormat::Bgra8UnormSrgb, false) => TextureFormat::Bgra8Unorm,
+        (format, _) => format,
+    }
+}
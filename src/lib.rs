@@ -0,0 +1,10 @@
+pub mod bench;
+pub mod components;
+pub mod config;
+pub mod graphics;
+pub mod resources;
+pub mod scenario;
+pub mod setup;
+pub mod shaders;
+pub mod systems;
+pub mod universe;
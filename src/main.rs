@@ -1,29 +1,13 @@
-use futures::executor::block_on;
 use tracing::metadata::LevelFilter;
 use tracing_log::LogTracer;
 use tracing_subscriber::EnvFilter;
-use wgpu::{
-    BackendBit, DeviceDescriptor, Features, Instance, Limits, PowerPreference,
-    RequestAdapterOptions,
-};
+use wgpu_bug_validation_errors::{bench::*, config::*, scenario::*, setup::*, universe::*};
 use winit::{
     event::{Event, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
 };
 
 
-mod components;
-mod graphics;
-mod resources;
-mod setup;
-mod shaders;
-mod systems;
-mod universe;
-
-use setup::*;
-use universe::*;
-
-
 fn main() {
     LogTracer::init().unwrap();
     let collector = tracing_subscriber::fmt()
@@ -32,35 +16,58 @@ fn main() {
         .finish();
     tracing::subscriber::set_global_default(collector).unwrap();
 
+    let (config, mode) = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if let Mode::Help = mode {
+        println!("{}", USAGE);
+        return;
+    }
+
+    if let Mode::ListAdapters = mode {
+        list_adapters(&config);
+        return;
+    }
+
+    if let Mode::Bench = mode {
+        let passed = run_bench(&config);
+        std::process::exit(if passed { 0 } else { 1 });
+    }
+
+    // runs the given scenarios headlessly and exits with an error if any captured errors
+    if let Mode::Scenarios(paths) = mode {
+        let passed = run_scenarios(&config, &paths);
+        std::process::exit(if passed { 0 } else { 1 });
+    }
+
     let eventloop = EventLoop::new();
-    let window = create_window(&eventloop);
+    let window = create_window(&eventloop, &config.window);
 
-    let backend = BackendBit::VULKAN;
-    let instance = Instance::new(backend);
-    let surface = unsafe { instance.create_surface(&window) };
-    let adapter_options = RequestAdapterOptions {
-        power_preference:   PowerPreference::HighPerformance,
-        compatible_surface: Some(&surface),
-    };
-    let adapter = block_on(instance.request_adapter(&adapter_options)).unwrap();
-    let device_limits = Limits {
-        max_push_constant_size: 128,
-        ..Limits::default()
+    let (adapter, surface) = match request_adapter(&config, Some(&window)) {
+        Some((adapter, surface)) => (adapter, surface.unwrap()),
+        None => {
+            tracing::error!("no adapter available for backends {:?}", config.backends);
+            std::process::exit(1);
+        }
     };
-    let device_features = Features::default() | Features::PUSH_CONSTANTS;
-    let device_descriptor = DeviceDescriptor {
-        limits:   device_limits,
-        features: device_features,
-        label:    None,
+    let (device, queue) = match request_device(&adapter, config.trace_path()) {
+        Ok(device) => device,
+        Err(e) => {
+            tracing::error!("{}", e);
+            std::process::exit(1);
+        }
     };
-    let (device, queue) = block_on(
-        adapter.request_device(&device_descriptor, Some(&std::path::Path::new("./trace"))),
-    )
-    .unwrap();
+
+    let format = adapter.get_swap_chain_preferred_format(&surface);
 
     window.set_visible(true);
 
-    let mut universe = Universe::new(device, queue, surface);
+    let mut universe = Universe::new(device, queue, Some(surface), format, &config);
     universe.resize(window.inner_size().width, window.inner_size().height);
 
     eventloop.run(move |event, _, control_flow| {
@@ -80,6 +87,7 @@ fn main() {
                 if window_id == window.id() {
                     match window_event {
                         WindowEvent::CloseRequested => {
+                            universe.stop_recording();
                             *control_flow = ControlFlow::Exit;
                         }
                         _ => {
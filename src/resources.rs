@@ -2,7 +2,7 @@ use std::{env, path, path::Path};
 
 use image::{DynamicImage, ImageResult};
 
-use crate::shaders;
+use crate::{graphics::texture::TextureConfig, shaders};
 
 
 pub fn get_path<P: AsRef<Path>>(path: P) -> path::PathBuf {
@@ -13,6 +13,18 @@ pub fn get_image<P: AsRef<Path>>(path: P) -> ImageResult<DynamicImage> {
     image::open(get_path("images").join(&path))
 }
 
+pub fn get_texture_config<P: AsRef<Path>>(path: P) -> Option<TextureConfig> {
+    let path = get_path("images").join(&path).with_extension("ron");
+    let config = std::fs::read_to_string(&path).ok()?;
+    match ron::from_str(&config) {
+        Ok(config) => Some(config),
+        Err(e) => {
+            tracing::warn!("invalid texture config {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
 pub fn get_shader<P: AsRef<Path>>(path: P) -> Result<Vec<u32>, shaderc::Error> {
     shaders::get_shader(&path)
 }
@@ -0,0 +1,167 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use shipyard::UniqueView;
+use wgpu::Maintain;
+
+use crate::{
+    config::Config,
+    graphics::{errors::ErrorRecord, renderer::Renderer},
+    resources::get_path,
+    setup::create_headless_universe,
+    universe::Scene,
+};
+
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Resize {
+    // applied before rendering this frame
+    pub frame:  u32,
+    pub width:  u32,
+    pub height: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scenario {
+    pub name:    String,
+    pub scene:   Scene,
+    pub width:   u32,
+    pub height:  u32,
+    pub frames:  u32,
+    pub resizes: Vec<Resize>,
+}
+impl Default for Scenario {
+    fn default() -> Self {
+        Self {
+            name:    "default".to_owned(),
+            scene:   Scene::default(),
+            width:   1024,
+            height:  1024,
+            frames:  10,
+            resizes: Vec::new(),
+        }
+    }
+}
+
+pub fn get_scenario<P: AsRef<Path>>(path: P) -> Option<Scenario> {
+    let path = path.as_ref();
+    let scenario = match std::fs::read_to_string(path) {
+        Ok(scenario) => scenario,
+        Err(e) => {
+            tracing::error!("failed to read scenario {:?}: {}", path, e);
+            return None;
+        }
+    };
+    match ron::from_str(&scenario) {
+        Ok(scenario) => Some(scenario),
+        Err(e) => {
+            tracing::error!("invalid scenario {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+// every scenario in data/scenarios
+pub fn get_scenario_paths() -> Vec<PathBuf> {
+    let mut paths = std::fs::read_dir(get_path("scenarios"))
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| {
+                    path.extension()
+                        .map_or(false, |extension| extension == "ron")
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    paths.sort();
+    paths
+}
+
+// runs the scenario without a window and returns the errors captured along the way, fails
+// if no device could be created
+pub fn run_scenario(
+    config: &Config, scenario: &Scenario, trace: Option<&Path>,
+) -> Result<Vec<ErrorRecord>, String> {
+    let config = Config {
+        trace: trace.is_some(),
+        trace_path: trace.map_or_else(|| config.trace_path.clone(), Path::to_path_buf),
+        scene: scenario.scene.clone(),
+        ..config.clone()
+    };
+    let mut universe = create_headless_universe(&config)?;
+    universe.resize(scenario.width, scenario.height);
+    for frame in 0..scenario.frames {
+        for resize in scenario
+            .resizes
+            .iter()
+            .filter(|resize| resize.frame == frame)
+        {
+            universe.resize(resize.width, resize.height);
+        }
+        universe.render();
+    }
+    if let Some(trace) = trace {
+        if let Err(e) = universe.save_scene(trace.join("scene.ron")) {
+            tracing::warn!("failed to save scene: {}", e);
+        }
+    }
+    Ok(universe.world.run(|renderer: UniqueView<Renderer>| {
+        renderer.device.poll(Maintain::Wait);
+        if let Some(trace) = trace {
+            if let Err(e) = renderer.errors.write_report(&trace.join("errors.json")) {
+                tracing::warn!("failed to write error report: {}", e);
+            }
+        }
+        renderer.errors.records()
+    }))
+}
+
+// failing scenarios are run a second time with a wgpu trace in <trace path>/scenarios
+pub fn run_scenarios(config: &Config, paths: &[String]) -> bool {
+    let paths = if paths.is_empty() {
+        get_scenario_paths()
+    } else {
+        paths.iter().map(PathBuf::from).collect()
+    };
+    let mut passed = true;
+    for path in paths {
+        let scenario = match get_scenario(&path) {
+            Some(scenario) => scenario,
+            None => {
+                passed = false;
+                continue;
+            }
+        };
+        let errors = match run_scenario(config, &scenario, None) {
+            Ok(errors) => errors,
+            Err(e) => {
+                tracing::error!("scenario {} could not run: {}", scenario.name, e);
+                passed = false;
+                continue;
+            }
+        };
+        if errors.is_empty() {
+            println!("scenario {} passed", scenario.name);
+            continue;
+        }
+        passed = false;
+        println!(
+            "scenario {} failed with {} errors",
+            scenario.name,
+            errors.len()
+        );
+        let trace = config.trace_path.join("scenarios").join(&scenario.name);
+        match run_scenario(config, &scenario, Some(&trace)) {
+            Ok(errors) => println!(
+                "scenario {} traced to {:?} with {} errors",
+                scenario.name,
+                trace,
+                errors.len()
+            ),
+            Err(e) => tracing::error!("scenario {} could not be traced: {}", scenario.name, e),
+        }
+    }
+    passed
+}
@@ -1,16 +1,134 @@
+use std::path::Path;
+
+use futures::executor::block_on;
+use wgpu::{
+    Adapter, Device, DeviceDescriptor, Instance, Queue, RequestAdapterOptions, Surface,
+    TextureFormat,
+};
 use winit::{
     dpi::LogicalSize,
-    window::{Window, WindowBuilder},
+    window::{Fullscreen, Window, WindowBuilder},
+};
+
+use crate::{
+    config::{AdapterSelector, Config, WindowConfig},
+    graphics::renderer::Renderer,
+    universe::Universe,
 };
 
 
-pub fn create_window(eventloop: &winit::event_loop::EventLoop<()>) -> Window {
-    let builder = WindowBuilder::new()
+pub fn create_window(
+    eventloop: &winit::event_loop::EventLoop<()>, config: &WindowConfig,
+) -> Window {
+    let mut builder = WindowBuilder::new()
         .with_resizable(true)
-        .with_inner_size(LogicalSize::new(1024, 1024))
-        .with_min_inner_size(LogicalSize::new(1024, 1024))
+        .with_inner_size(LogicalSize::new(config.width, config.height))
         .with_title(env!("CARGO_PKG_NAME"))
         .with_transparent(false)
         .with_decorations(true);
+    if let Some((width, height)) = config.min_size {
+        builder = builder.with_min_inner_size(LogicalSize::new(width, height));
+    }
+    if config.fullscreen {
+        builder = builder.with_fullscreen(Some(Fullscreen::Borderless(None)));
+    }
     builder.build(&eventloop).unwrap()
 }
+
+pub fn list_adapters(config: &Config) {
+    let instance = Instance::new(config.backend_bits());
+    for (index, adapter) in instance
+        .enumerate_adapters(config.backend_bits())
+        .enumerate()
+    {
+        let info = adapter.get_info();
+        println!(
+            "{}: {} ({:?}, {:?})",
+            index, info.name, info.backend, info.device_type
+        );
+        println!("    features: {:?}", adapter.features());
+        println!("    limits: {:?}", adapter.limits());
+    }
+}
+
+fn find_adapter(config: &Config, selector: &AdapterSelector) -> Option<(Instance, Adapter)> {
+    let instance = Instance::new(config.backend_bits());
+    let adapter = instance
+        .enumerate_adapters(config.backend_bits())
+        .enumerate()
+        .find(|(index, adapter)| match selector {
+            AdapterSelector::Index(selected) => index == selected,
+            AdapterSelector::Name(name) => adapter
+                .get_info()
+                .name
+                .to_lowercase()
+                .contains(&name.to_lowercase()),
+        })
+        .map(|(_, adapter)| adapter);
+    adapter.map(|adapter| (instance, adapter))
+}
+
+// uses the selected adapter or tries each configured backend in order,
+// the surface is created from the instance the adapter belongs to
+pub fn request_adapter(
+    config: &Config, window: Option<&Window>,
+) -> Option<(Adapter, Option<Surface>)> {
+    if let Some(selector) = &config.adapter {
+        return match find_adapter(config, selector) {
+            Some((instance, adapter)) => {
+                tracing::info!("using adapter {:?}", adapter.get_info());
+                let surface = window.map(|window| unsafe { instance.create_surface(window) });
+                Some((adapter, surface))
+            }
+            None => {
+                tracing::error!("no adapter matches {:?}", selector);
+                None
+            }
+        };
+    }
+    for backend in config.backends.iter() {
+        let instance = Instance::new((*backend).into());
+        let surface = window.map(|window| unsafe { instance.create_surface(window) });
+        let adapter_options = RequestAdapterOptions {
+            power_preference:   config.power.into(),
+            compatible_surface: surface.as_ref(),
+        };
+        match block_on(instance.request_adapter(&adapter_options)) {
+            Some(adapter) => {
+                tracing::info!("using adapter {:?}", adapter.get_info());
+                return Some((adapter, surface));
+            }
+            None => tracing::warn!("no {:?} adapter available", backend),
+        }
+    }
+    None
+}
+
+pub fn request_device(adapter: &Adapter, trace: Option<&Path>) -> Result<(Device, Queue), String> {
+    let (device_features, device_limits) = Renderer::negotiate(adapter)?;
+    let device_descriptor = DeviceDescriptor {
+        limits:   device_limits,
+        features: device_features,
+        label:    None,
+    };
+    if let Some(trace) = trace {
+        if let Err(error) = std::fs::create_dir_all(trace) {
+            tracing::warn!("failed to create trace directory {:?}: {}", trace, error);
+        }
+    }
+    block_on(adapter.request_device(&device_descriptor, trace))
+        .map_err(|e| format!("failed to create device: {}", e))
+}
+
+// a universe rendering into an offscreen target instead of a window
+pub fn create_headless_universe(config: &Config) -> Result<Universe, String> {
+    let (adapter, _) = request_adapter(config, None).ok_or("no adapter available")?;
+    let (device, queue) = request_device(&adapter, config.trace_path())?;
+    Ok(Universe::new(
+        device,
+        queue,
+        None,
+        TextureFormat::Bgra8Unorm,
+        config,
+    ))
+}
@@ -0,0 +1,588 @@
+use std::collections::BTreeMap;
+
+use shipyard::{EntityId, Get, IntoIter, IntoWithId, UniqueViewMut, View};
+use ultraviolet::Vec2;
+
+use crate::components::{Collider, GlobalTransform, Shape, Sprite, SpriteData, Transform};
+
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Contact {
+    // points from the first entity of the pair to the second
+    pub normal: Vec2,
+    pub depth:  f32,
+}
+impl Contact {
+    fn flipped(self) -> Self {
+        Self {
+            normal: -self.normal,
+            ..self
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CollisionKind {
+    Start,
+    Stay,
+    End,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CollisionEvent {
+    pub kind:    CollisionKind,
+    // ordered by entity id
+    pub a:       EntityId,
+    pub b:       EntityId,
+    // none when the collision ended
+    pub contact: Option<Contact>,
+    pub trigger: bool,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Body {
+    Box {
+        center: Vec2,
+        axes:   [Vec2; 2],
+        half:   Vec2,
+    },
+    Circle {
+        center: Vec2,
+        radius: f32,
+    },
+}
+impl Body {
+    // centered on the sprite quad, which is placed around its pivot in sprite/simple.vert
+    fn new(collider: &Collider, transform: &Transform, pivot: Vec2) -> Self {
+        let (sin, cos) = transform.angle().sin_cos();
+        let offset =
+            (Vec2::broadcast(0.5) - pivot) * 2.0 * Vec2::new(transform.size.x, transform.size.y);
+        let center = Vec2::new(
+            transform.position.x + offset.x * cos - offset.y * sin,
+            transform.position.y + offset.x * sin + offset.y * cos,
+        );
+        let half = Vec2::new(transform.size.x.abs(), transform.size.y.abs());
+        match collider.shape {
+            Shape::Aabb => Body::Box {
+                center,
+                axes: [Vec2::unit_x(), Vec2::unit_y()],
+                half,
+            },
+            Shape::Obb => Body::Box {
+                center,
+                axes: [Vec2::new(cos, sin), Vec2::new(-sin, cos)],
+                half,
+            },
+            Shape::Circle => Body::Circle {
+                center,
+                radius: half.x.max(half.y),
+            },
+        }
+    }
+
+    fn bounds(&self) -> (Vec2, Vec2) {
+        let (center, extent) = match *self {
+            Body::Box { center, axes, half } => (
+                center,
+                Vec2::new(
+                    axes[0].x.abs() * half.x + axes[1].x.abs() * half.y,
+                    axes[0].y.abs() * half.x + axes[1].y.abs() * half.y,
+                ),
+            ),
+            Body::Circle { center, radius } => (center, Vec2::broadcast(radius)),
+        };
+        (center - extent, center + extent)
+    }
+}
+
+// separating axis test over the four box axes
+fn box_box(a: (Vec2, [Vec2; 2], Vec2), b: (Vec2, [Vec2; 2], Vec2)) -> Option<Contact> {
+    let offset = b.0 - a.0;
+    let mut contact = Contact {
+        normal: Vec2::zero(),
+        depth:  f32::MAX,
+    };
+    for axis in a.1.iter().chain(b.1.iter()) {
+        let radius = |(_, axes, half): (Vec2, [Vec2; 2], Vec2)| {
+            half.x * axes[0].dot(*axis).abs() + half.y * axes[1].dot(*axis).abs()
+        };
+        let distance = offset.dot(*axis);
+        let depth = radius(a) + radius(b) - distance.abs();
+        if depth <= 0.0 {
+            return None;
+        }
+        if depth < contact.depth {
+            contact = Contact {
+                normal: if distance < 0.0 { -*axis } else { *axis },
+                depth,
+            };
+        }
+    }
+    Some(contact)
+}
+
+fn circle_circle(a: (Vec2, f32), b: (Vec2, f32)) -> Option<Contact> {
+    let offset = b.0 - a.0;
+    let distance = offset.mag();
+    let depth = a.1 + b.1 - distance;
+    if depth <= 0.0 {
+        return None;
+    }
+    Some(Contact {
+        normal: if distance > 0.0 {
+            offset / distance
+        } else {
+            Vec2::unit_x()
+        },
+        depth,
+    })
+}
+
+// the normal points from the box to the circle
+fn box_circle(a: (Vec2, [Vec2; 2], Vec2), b: (Vec2, f32)) -> Option<Contact> {
+    let (center, axes, half) = a;
+    let offset = b.0 - center;
+    let local = Vec2::new(offset.dot(axes[0]), offset.dot(axes[1]));
+    let closest = Vec2::new(
+        local.x.max(-half.x).min(half.x),
+        local.y.max(-half.y).min(half.y),
+    );
+    if closest != local {
+        let delta = local - closest;
+        let distance = delta.mag();
+        let depth = b.1 - distance;
+        if depth <= 0.0 {
+            return None;
+        }
+        let normal = delta / distance;
+        return Some(Contact {
+            normal: axes[0] * normal.x + axes[1] * normal.y,
+            depth,
+        });
+    }
+    // the circle center is inside the box, push it out along the nearest face
+    let (x, y) = (half.x - local.x.abs(), half.y - local.y.abs());
+    Some(if x < y {
+        Contact {
+            normal: axes[0] * local.x.signum(),
+            depth:  b.1 + x,
+        }
+    } else {
+        Contact {
+            normal: axes[1] * local.y.signum(),
+            depth:  b.1 + y,
+        }
+    })
+}
+
+fn narrowphase(a: &Body, b: &Body) -> Option<Contact> {
+    match (*a, *b) {
+        (
+            Body::Box {
+                center: ca,
+                axes: aa,
+                half: ha,
+            },
+            Body::Box {
+                center: cb,
+                axes: ab,
+                half: hb,
+            },
+        ) => box_box((ca, aa, ha), (cb, ab, hb)),
+        (
+            Body::Circle {
+                center: ca,
+                radius: ra,
+            },
+            Body::Circle {
+                center: cb,
+                radius: rb,
+            },
+        ) => circle_circle((ca, ra), (cb, rb)),
+        (Body::Box { center, axes, half }, Body::Circle { center: cb, radius }) => {
+            box_circle((center, axes, half), (cb, radius))
+        }
+        (Body::Circle { center: ca, radius }, Body::Box { center, axes, half }) => {
+            box_circle((center, axes, half), (ca, radius)).map(Contact::flipped)
+        }
+    }
+}
+
+// contacts between colliders found with a sorted spatial hash, and the events since last frame
+#[derive(Default)]
+pub struct Collisions {
+    pub events:    Vec<CollisionEvent>,
+    // spatial hash cell size, twice the mean collider extent when unset
+    pub cell_size: Option<f32>,
+
+    // ordered so events are emitted in the same order every run
+    contacts: BTreeMap<(EntityId, EntityId), (Contact, bool)>,
+    previous: BTreeMap<(EntityId, EntityId), (Contact, bool)>,
+    bodies:   Vec<(EntityId, Collider, Body, (Vec2, Vec2))>,
+    cells:    Vec<(i32, i32, u32)>,
+}
+impl Collisions {
+    pub fn contact(&self, a: EntityId, b: EntityId) -> Option<Contact> {
+        if a < b {
+            self.contacts.get(&(a, b)).map(|(contact, _)| *contact)
+        } else {
+            self.contacts
+                .get(&(b, a))
+                .map(|(contact, _)| contact.flipped())
+        }
+    }
+
+    pub fn contacts(&self) -> impl Iterator<Item = (EntityId, EntityId, Contact)> + '_ {
+        self.contacts
+            .iter()
+            .map(|((a, b), (contact, _))| (*a, *b, *contact))
+    }
+
+    // sprites are only read for their pivot, colliders without one are centered
+    pub fn update(
+        &mut self, colliders: &View<Collider>, transforms: &View<GlobalTransform>,
+        sprites: &View<Sprite>,
+    ) {
+        std::mem::swap(&mut self.contacts, &mut self.previous);
+        self.contacts.clear();
+        self.events.clear();
+
+        self.bodies.clear();
+        for (id, (collider, transform)) in (colliders, transforms).iter().with_id() {
+            let pivot = sprites
+                .get(id)
+                .map_or(SpriteData::PIVOT_CENTER, |sprite| sprite.data.pivot);
+            let body = Body::new(collider, &transform.0, pivot);
+            self.bodies.push((id, *collider, body, body.bounds()));
+        }
+
+        let cell_size = self.cell_size.unwrap_or_else(|| {
+            let extent = self
+                .bodies
+                .iter()
+                .map(|(.., (min, max))| (max.x - min.x).max(max.y - min.y))
+                .sum::<f32>();
+            2.0 * extent / self.bodies.len().max(1) as f32
+        });
+        let cell_size = cell_size.max(f32::EPSILON);
+        let cell = |point: Vec2| {
+            (
+                (point.x / cell_size).floor() as i32,
+                (point.y / cell_size).floor() as i32,
+            )
+        };
+
+        self.cells.clear();
+        for (index, (.., (min, max))) in self.bodies.iter().enumerate() {
+            let ((x0, y0), (x1, y1)) = (cell(*min), cell(*max));
+            for x in x0..=x1 {
+                for y in y0..=y1 {
+                    self.cells.push((x, y, index as u32));
+                }
+            }
+        }
+        self.cells.sort_unstable();
+
+        let mut start = 0;
+        while start < self.cells.len() {
+            let (x, y, _) = self.cells[start];
+            let end = start
+                + self.cells[start..]
+                    .iter()
+                    .take_while(|(cx, cy, _)| *cx == x && *cy == y)
+                    .count();
+            for i in start..end {
+                for j in i + 1..end {
+                    let (a, b) = (
+                        &self.bodies[self.cells[i].2 as usize],
+                        &self.bodies[self.cells[j].2 as usize],
+                    );
+                    let ((min_a, max_a), (min_b, max_b)) = (a.3, b.3);
+                    if max_a.x < min_b.x
+                        || max_b.x < min_a.x
+                        || max_a.y < min_b.y
+                        || max_b.y < min_a.y
+                        || !a.1.interacts(&b.1)
+                    {
+                        continue;
+                    }
+                    // pairs sharing several cells are only tested in the cell holding the
+                    // minimum corner of their overlap
+                    if cell(min_a.max_by_component(min_b)) != (x, y) {
+                        continue;
+                    }
+                    let contact = match narrowphase(&a.2, &b.2) {
+                        Some(contact) => contact,
+                        None => continue,
+                    };
+                    let trigger = a.1.trigger || b.1.trigger;
+                    if a.0 < b.0 {
+                        self.contacts.insert((a.0, b.0), (contact, trigger));
+                    } else {
+                        self.contacts
+                            .insert((b.0, a.0), (contact.flipped(), trigger));
+                    }
+                }
+            }
+            start = end;
+        }
+
+        for ((a, b), (contact, trigger)) in self.contacts.iter() {
+            self.events.push(CollisionEvent {
+                kind:    if self.previous.contains_key(&(*a, *b)) {
+                    CollisionKind::Stay
+                } else {
+                    CollisionKind::Start
+                },
+                a:       *a,
+                b:       *b,
+                contact: Some(*contact),
+                trigger: *trigger,
+            });
+        }
+        for ((a, b), (_, trigger)) in self.previous.iter() {
+            if !self.contacts.contains_key(&(*a, *b)) {
+                self.events.push(CollisionEvent {
+                    kind:    CollisionKind::End,
+                    a:       *a,
+                    b:       *b,
+                    contact: None,
+                    trigger: *trigger,
+                });
+            }
+        }
+    }
+}
+
+pub fn collide(
+    mut collisions: UniqueViewMut<Collisions>, colliders: View<Collider>,
+    transforms: View<GlobalTransform>, sprites: View<Sprite>,
+) {
+    collisions.update(&colliders, &transforms, &sprites);
+}
+
+#[cfg(test)]
+mod tests {
+    use shipyard::{ViewMut, World};
+    use ultraviolet::Vec3;
+
+    use super::*;
+
+
+    fn approx(a: Vec2, b: Vec2) -> bool { (a - b).mag() < 1e-4 }
+
+    fn aabb(center: Vec2, half: Vec2) -> (Vec2, [Vec2; 2], Vec2) {
+        (center, [Vec2::unit_x(), Vec2::unit_y()], half)
+    }
+
+    #[test]
+    fn box_box_overlap() {
+        let contact = box_box(
+            aabb(Vec2::zero(), Vec2::one()),
+            aabb(Vec2::new(1.5, 0.25), Vec2::one()),
+        )
+        .unwrap();
+        assert!(approx(contact.normal, Vec2::unit_x()));
+        assert!((contact.depth - 0.5).abs() < 1e-4);
+
+        let contact = box_box(
+            aabb(Vec2::zero(), Vec2::one()),
+            aabb(Vec2::new(0.25, -1.75), Vec2::one()),
+        )
+        .unwrap();
+        assert!(approx(contact.normal, -Vec2::unit_y()));
+        assert!((contact.depth - 0.25).abs() < 1e-4);
+    }
+
+    #[test]
+    fn box_box_separated() {
+        assert!(box_box(
+            aabb(Vec2::zero(), Vec2::one()),
+            aabb(Vec2::new(2.5, 0.0), Vec2::one())
+        )
+        .is_none());
+        // touching boxes don't collide
+        assert!(box_box(
+            aabb(Vec2::zero(), Vec2::one()),
+            aabb(Vec2::new(2.0, 0.0), Vec2::one())
+        )
+        .is_none());
+        // the corner of a rotated box passes the box axes but not its own
+        let (sin, cos) = std::f32::consts::FRAC_PI_4.sin_cos();
+        let rotated = (
+            Vec2::new(2.2, 2.2),
+            [Vec2::new(cos, sin), Vec2::new(-sin, cos)],
+            Vec2::one(),
+        );
+        assert!(box_box(aabb(Vec2::zero(), Vec2::one()), rotated).is_none());
+    }
+
+    #[test]
+    fn box_box_rotated() {
+        let (sin, cos) = std::f32::consts::FRAC_PI_4.sin_cos();
+        let rotated = (
+            Vec2::new(2.0, 0.0),
+            [Vec2::new(cos, sin), Vec2::new(-sin, cos)],
+            Vec2::one(),
+        );
+        let contact = box_box(aabb(Vec2::zero(), Vec2::one()), rotated).unwrap();
+        assert!(approx(contact.normal, Vec2::unit_x()));
+        assert!((contact.depth - (2.0f32.sqrt() - 1.0)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn circle_circle_overlap() {
+        let contact = circle_circle((Vec2::zero(), 1.0), (Vec2::new(0.0, 1.5), 1.0)).unwrap();
+        assert!(approx(contact.normal, Vec2::unit_y()));
+        assert!((contact.depth - 0.5).abs() < 1e-4);
+        assert!(circle_circle((Vec2::zero(), 1.0), (Vec2::new(2.5, 0.0), 1.0)).is_none());
+        // coincident centers still get a unit normal
+        let contact = circle_circle((Vec2::zero(), 1.0), (Vec2::zero(), 0.5)).unwrap();
+        assert!(approx(contact.normal, Vec2::unit_x()));
+        assert!((contact.depth - 1.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn box_circle_overlap() {
+        let contact =
+            box_circle(aabb(Vec2::zero(), Vec2::one()), (Vec2::new(-1.5, 0.0), 1.0)).unwrap();
+        assert!(approx(contact.normal, -Vec2::unit_x()));
+        assert!((contact.depth - 0.5).abs() < 1e-4);
+        // near a corner the normal points from the corner to the center
+        let contact =
+            box_circle(aabb(Vec2::zero(), Vec2::one()), (Vec2::new(1.5, 1.5), 1.0)).unwrap();
+        assert!(approx(contact.normal, Vec2::one().normalized()));
+        assert!(box_circle(aabb(Vec2::zero(), Vec2::one()), (Vec2::new(1.8, 1.8), 1.0)).is_none());
+    }
+
+    #[test]
+    fn box_circle_inside() {
+        let contact = box_circle(
+            aabb(Vec2::zero(), Vec2::new(2.0, 1.0)),
+            (Vec2::new(0.5, 0.75), 0.5),
+        )
+        .unwrap();
+        assert!(approx(contact.normal, Vec2::unit_y()));
+        assert!((contact.depth - 0.75).abs() < 1e-4);
+    }
+
+    #[test]
+    fn narrowphase_normals_point_from_a_to_b() {
+        let square = Body::Box {
+            center: Vec2::zero(),
+            axes:   [Vec2::unit_x(), Vec2::unit_y()],
+            half:   Vec2::one(),
+        };
+        let circle = Body::Circle {
+            center: Vec2::new(1.5, 0.0),
+            radius: 1.0,
+        };
+        let contact = narrowphase(&square, &circle).unwrap();
+        assert!(approx(contact.normal, Vec2::unit_x()));
+        let contact = narrowphase(&circle, &square).unwrap();
+        assert!(approx(contact.normal, -Vec2::unit_x()));
+    }
+
+    #[test]
+    fn body_is_centered_on_the_quad() {
+        let transform = Transform::new(Vec3::new(1.0, 1.0, 0.0), Vec2::new(1.0, 2.0));
+        let collider = Collider::new(Shape::Obb);
+        match Body::new(&collider, &transform, SpriteData::PIVOT_CENTER) {
+            Body::Box { center, .. } => assert!(approx(center, Vec2::one())),
+            body => panic!("unexpected body {:?}", body),
+        }
+        match Body::new(&collider, &transform, SpriteData::PIVOT_BOTTOM) {
+            Body::Box { center, .. } => assert!(approx(center, Vec2::new(1.0, 3.0))),
+            body => panic!("unexpected body {:?}", body),
+        }
+        let rotated = transform.with_degrees(90.0);
+        match Body::new(&collider, &rotated, SpriteData::PIVOT_BOTTOM) {
+            Body::Box { center, .. } => assert!(approx(center, Vec2::new(-1.0, 1.0))),
+            body => panic!("unexpected body {:?}", body),
+        }
+    }
+
+    fn update(world: &World, collisions: &mut Collisions) {
+        world.run(
+            |colliders: View<Collider>,
+             transforms: View<GlobalTransform>,
+             sprites: View<Sprite>| {
+                collisions.update(&colliders, &transforms, &sprites)
+            },
+        );
+    }
+
+    #[test]
+    fn pairs_spanning_cells_are_found_once() {
+        let mut world = World::new();
+        let entities = [
+            Vec3::new(-1.2, -0.9, 0.0),
+            Vec3::new(0.3, 0.4, 0.0),
+            Vec3::new(-0.6, 0.9, 0.0),
+        ]
+        .iter()
+        .map(|position| {
+            world.add_entity((
+                Collider::new(Shape::Aabb),
+                GlobalTransform(Transform::new(*position, Vec2::one())),
+            ))
+        })
+        .collect::<Vec<_>>();
+        let mut collisions = Collisions {
+            cell_size: Some(0.3),
+            ..Collisions::default()
+        };
+        update(&world, &mut collisions);
+
+        let pairs = collisions
+            .events
+            .iter()
+            .map(|event| (event.kind, event.a, event.b))
+            .collect::<Vec<_>>();
+        let mut expected = vec![
+            (CollisionKind::Start, entities[0], entities[1]),
+            (CollisionKind::Start, entities[0], entities[2]),
+            (CollisionKind::Start, entities[1], entities[2]),
+        ];
+        expected.sort_by_key(|(_, a, b)| (*a, *b));
+        assert_eq!(pairs, expected);
+    }
+
+    #[test]
+    fn events_follow_contacts() {
+        let mut world = World::new();
+        let a = world.add_entity((
+            Collider::new(Shape::Circle),
+            GlobalTransform(Transform::new(Vec3::zero(), Vec2::one())),
+        ));
+        let b = world.add_entity((
+            Collider::new(Shape::Circle),
+            GlobalTransform(Transform::new(Vec3::new(1.5, 0.0, 0.0), Vec2::one())),
+        ));
+        let mut collisions = Collisions::default();
+        let kinds = |collisions: &Collisions| {
+            collisions
+                .events
+                .iter()
+                .map(|event| event.kind)
+                .collect::<Vec<_>>()
+        };
+
+        update(&world, &mut collisions);
+        assert_eq!(kinds(&collisions), vec![CollisionKind::Start]);
+        assert!(approx(
+            collisions.contact(b, a).unwrap().normal,
+            -Vec2::unit_x()
+        ));
+        update(&world, &mut collisions);
+        assert_eq!(kinds(&collisions), vec![CollisionKind::Stay]);
+
+        world.run(|mut transforms: ViewMut<GlobalTransform>| {
+            (&mut transforms).get(b).unwrap().0.position.x = 3.0;
+        });
+        update(&world, &mut collisions);
+        assert_eq!(kinds(&collisions), vec![CollisionKind::End]);
+        update(&world, &mut collisions);
+        assert!(collisions.events.is_empty());
+    }
+}
@@ -0,0 +1,276 @@
+use shipyard::{
+    AllStorages, EntitiesView, EntityId, Get, IntoIter, IntoWithId, Remove, View, ViewMut,
+};
+
+use crate::components::{Children, GlobalTransform, LocalTransform, Parent, Transform};
+
+
+fn set_global(
+    entities: &EntitiesView, globals: &mut ViewMut<GlobalTransform>, entity: EntityId,
+    transform: Transform,
+) {
+    match (&mut *globals).get(entity) {
+        Ok(global) => global.0 = transform,
+        Err(_) => entities.add_component(entity, &mut *globals, GlobalTransform(transform)),
+    }
+}
+
+// roots and children without a LocalTransform are placed by their Transform,
+// other children are composed down from their parent's world transform
+pub fn propagate_transforms(
+    entities: EntitiesView, parents: View<Parent>, children: View<Children>,
+    transforms: View<Transform>, locals: View<LocalTransform>,
+    mut globals: ViewMut<GlobalTransform>,
+) {
+    for (id, transform) in (&transforms).iter().with_id() {
+        if (&parents).get(id).is_err() || (&locals).get(id).is_err() {
+            set_global(&entities, &mut globals, id, *transform);
+        }
+    }
+
+    let mut stack = Vec::new();
+    for (root, _) in (&children)
+        .iter()
+        .with_id()
+        .filter(|(id, _)| (&parents).get(*id).is_err())
+    {
+        stack.push(root);
+        while let Some(parent) = stack.pop() {
+            let global = match (&globals).get(parent) {
+                Ok(global) => global.0,
+                Err(_) => continue,
+            };
+            let descendants = match (&children).get(parent) {
+                Ok(descendants) => descendants,
+                Err(_) => continue,
+            };
+            for child in descendants.0.iter() {
+                if let Ok(local) = (&locals).get(*child) {
+                    set_global(&entities, &mut globals, *child, global.compose(&local.0));
+                }
+                stack.push(*child);
+            }
+        }
+    }
+}
+
+fn is_descendant(entity: EntityId, ancestor: EntityId, parents: &ViewMut<Parent>) -> bool {
+    let mut current = entity;
+    while let Ok(Parent(parent)) = parents.get(current) {
+        if *parent == ancestor {
+            return true;
+        }
+        current = *parent;
+    }
+    false
+}
+
+// moves the child under the parent keeping its world transform,
+// returns false if the parent is the child or one of its descendants
+pub fn attach(all_storages: &AllStorages, child: EntityId, parent: EntityId) -> bool {
+    let (entities, mut parents, mut children, transforms, globals, mut locals) = all_storages
+        .borrow::<(
+            EntitiesView,
+            ViewMut<Parent>,
+            ViewMut<Children>,
+            View<Transform>,
+            View<GlobalTransform>,
+            ViewMut<LocalTransform>,
+        )>()
+        .unwrap();
+    if child == parent || is_descendant(parent, child, &parents) {
+        return false;
+    }
+    detach_child(child, &mut parents, &mut children);
+
+    entities.add_component(child, &mut parents, Parent(parent));
+    match (&mut children).get(parent) {
+        Ok(siblings) => siblings.0.push(child),
+        Err(_) => entities.add_component(parent, &mut children, Children(vec![child])),
+    }
+    // entities spawned since the last propagation only have their Transform
+    let world = |entity: EntityId| {
+        (&globals)
+            .get(entity)
+            .map(|global| global.0)
+            .or_else(|_| (&transforms).get(entity).map(|transform| *transform))
+    };
+    if let (Ok(transform), Ok(parent_transform)) = (world(child), world(parent)) {
+        entities.add_component(
+            child,
+            &mut locals,
+            LocalTransform(transform.relative_to(&parent_transform)),
+        );
+    }
+    true
+}
+
+fn detach_child(child: EntityId, parents: &mut ViewMut<Parent>, children: &mut ViewMut<Children>) {
+    if let Some(Parent(parent)) = parents.remove(child) {
+        if let Ok(siblings) = (&mut *children).get(parent) {
+            siblings.0.retain(|sibling| *sibling != child);
+        }
+    }
+}
+
+// makes the child a root placed where its world transform was last propagated
+pub fn detach(all_storages: &AllStorages, child: EntityId) {
+    let (entities, mut parents, mut children, mut transforms, globals, mut locals) = all_storages
+        .borrow::<(
+            EntitiesView,
+            ViewMut<Parent>,
+            ViewMut<Children>,
+            ViewMut<Transform>,
+            View<GlobalTransform>,
+            ViewMut<LocalTransform>,
+        )>()
+        .unwrap();
+    detach_child(child, &mut parents, &mut children);
+    locals.remove(child);
+    if let Ok(global) = (&globals).get(child) {
+        match (&mut transforms).get(child) {
+            Ok(transform) => *transform = global.0,
+            Err(_) => entities.add_component(child, &mut transforms, global.0),
+        }
+    }
+}
+
+// deletes the entity together with all of its descendants
+pub fn despawn(all_storages: &mut AllStorages, entity: EntityId) {
+    let mut despawned = vec![entity];
+    {
+        let (mut parents, mut children) = all_storages
+            .borrow::<(ViewMut<Parent>, ViewMut<Children>)>()
+            .unwrap();
+        detach_child(entity, &mut parents, &mut children);
+        let mut index = 0;
+        while index < despawned.len() {
+            if let Ok(descendants) = (&children).get(despawned[index]) {
+                despawned.extend_from_slice(&descendants.0);
+            }
+            index += 1;
+        }
+    }
+    for entity in despawned {
+        all_storages.delete_entity(entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shipyard::{AllStoragesViewMut, World};
+    use ultraviolet::{Vec2, Vec3};
+
+    use super::*;
+
+
+    fn approx(a: &Transform, b: &Transform) -> bool {
+        let angle =
+            |transform: &Transform| Vec2::new(transform.angle().cos(), transform.angle().sin());
+        (a.position - b.position).mag() < 1e-4 && (angle(a) - angle(b)).mag() < 1e-4
+    }
+
+    fn global(world: &World, entity: EntityId) -> Transform {
+        world.run(|globals: View<GlobalTransform>| (&globals).get(entity).unwrap().0)
+    }
+
+    fn attach_to(world: &World, child: EntityId, parent: EntityId) -> bool {
+        world.run(|all_storages: AllStoragesViewMut| attach(&all_storages, child, parent))
+    }
+
+    #[test]
+    fn relative_to_inverts_compose() {
+        let parent = Transform::new(Vec3::new(1.0, -2.0, 1.0), Vec2::one()).with_degrees(30.0);
+        let child = Transform::new(Vec3::new(-0.5, 3.0, 2.0), Vec2::one()).with_degrees(-75.0);
+        let local = child.relative_to(&parent);
+        assert!(approx(&parent.compose(&local), &child));
+        assert!(approx(&local.relative_to(&Transform::default()), &local));
+    }
+
+    #[test]
+    fn children_compose_down_the_hierarchy() {
+        let mut world = World::new();
+        let root = world.add_entity((
+            Transform::new(Vec3::new(1.0, 0.0, 1.0), Vec2::one()).with_degrees(90.0),
+        ));
+        let child = world.add_entity((
+            Transform::default(),
+            LocalTransform(Transform::new(Vec3::new(1.0, 0.0, 1.0), Vec2::one())),
+        ));
+        let grandchild = world.add_entity((LocalTransform(
+            Transform::new(Vec3::new(0.0, 1.0, 0.0), Vec2::one()).with_degrees(90.0),
+        ),));
+        assert!(attach_to(&world, child, root));
+        assert!(attach_to(&world, grandchild, child));
+        // attaching keeps the world transform, so the locals have to be set again
+        world.run(|mut locals: ViewMut<LocalTransform>| {
+            (&mut locals).get(child).unwrap().0 =
+                Transform::new(Vec3::new(1.0, 0.0, 1.0), Vec2::one());
+            (&mut locals).get(grandchild).unwrap().0 =
+                Transform::new(Vec3::new(0.0, 1.0, 0.0), Vec2::one()).with_degrees(90.0);
+        });
+        world.run(propagate_transforms);
+
+        assert!(approx(
+            &global(&world, root),
+            &Transform::new(Vec3::new(1.0, 0.0, 1.0), Vec2::one()).with_degrees(90.0)
+        ));
+        assert!(approx(
+            &global(&world, child),
+            &Transform::new(Vec3::new(1.0, 1.0, 2.0), Vec2::one()).with_degrees(90.0)
+        ));
+        assert!(approx(
+            &global(&world, grandchild),
+            &Transform::new(Vec3::new(0.0, 1.0, 2.0), Vec2::one()).with_degrees(180.0)
+        ));
+    }
+
+    #[test]
+    fn reparenting_keeps_the_world_transform() {
+        let mut world = World::new();
+        let a = world.add_entity((
+            Transform::new(Vec3::new(2.0, 0.0, 0.0), Vec2::one()).with_degrees(45.0),
+        ));
+        let b = world.add_entity((Transform::new(Vec3::new(-1.0, 3.0, 0.0), Vec2::one()),));
+        let child = world.add_entity((
+            Transform::new(Vec3::new(0.5, 0.5, 1.0), Vec2::one()).with_degrees(10.0),
+        ));
+        world.run(propagate_transforms);
+        let placed = global(&world, child);
+
+        assert!(attach_to(&world, child, a));
+        world.run(propagate_transforms);
+        assert!(approx(&global(&world, child), &placed));
+
+        assert!(attach_to(&world, child, b));
+        world.run(propagate_transforms);
+        assert!(approx(&global(&world, child), &placed));
+        world.run(|children: View<Children>, parents: View<Parent>| {
+            assert!((&children).get(a).unwrap().0.is_empty());
+            assert_eq!((&children).get(b).unwrap().0, vec![child]);
+            assert_eq!(*(&parents).get(child).unwrap(), Parent(b));
+        });
+
+        // moving the parent moves the child with it
+        world.run(|mut transforms: ViewMut<Transform>| {
+            (&mut transforms).get(b).unwrap().position.x += 1.0;
+        });
+        world.run(propagate_transforms);
+        assert!((global(&world, child).position.x - placed.position.x - 1.0).abs() < 1e-4);
+
+        world.run(|all_storages: AllStoragesViewMut| detach(&all_storages, child));
+        world.run(propagate_transforms);
+        assert!((global(&world, child).position.x - placed.position.x - 1.0).abs() < 1e-4);
+        world.run(|parents: View<Parent>| assert!((&parents).get(child).is_err()));
+    }
+
+    #[test]
+    fn cycles_are_rejected() {
+        let mut world = World::new();
+        let parent = world.add_entity((Transform::default(),));
+        let child = world.add_entity((Transform::default(),));
+        assert!(attach_to(&world, child, parent));
+        assert!(!attach_to(&world, parent, child));
+        assert!(!attach_to(&world, parent, parent));
+    }
+}
@@ -1,2 +1,10 @@
+pub mod collision;
+pub mod hierarchy;
+pub mod motion;
+pub mod picking;
 pub mod render;
+pub use collision::*;
+pub use hierarchy::*;
+pub use motion::*;
+pub use picking::*;
 pub use render::*;
@@ -0,0 +1,144 @@
+use shipyard::{Get, IntoIter, IntoWithId, UniqueView, View, ViewMut};
+
+use crate::components::{
+    Acceleration, AngularVelocity, Damping, LocalTransform, Time, Transform, Velocity,
+};
+
+
+// semi-implicit euler, entities with a LocalTransform move relative to their parent
+pub fn integrate(
+    time: UniqueView<Time>, mut velocities: ViewMut<Velocity>,
+    mut angular_velocities: ViewMut<AngularVelocity>, accelerations: View<Acceleration>,
+    dampings: View<Damping>, mut transforms: ViewMut<Transform>,
+    mut locals: ViewMut<LocalTransform>,
+) {
+    let delta = time.delta;
+    if delta <= 0.0 {
+        return;
+    }
+
+    for (velocity, acceleration) in (&mut velocities, &accelerations).iter() {
+        velocity.0 += acceleration.0 * delta;
+    }
+    for (velocity, damping) in (&mut velocities, &dampings).iter() {
+        velocity.0 *= (-damping.linear * delta).exp();
+    }
+    for (angular_velocity, damping) in (&mut angular_velocities, &dampings).iter() {
+        angular_velocity.0 *= (-damping.angular * delta).exp();
+    }
+
+    for (id, velocity) in (&velocities).iter().with_id() {
+        let transform = match (&mut locals).get(id) {
+            Ok(local) => &mut local.0,
+            Err(_) => match (&mut transforms).get(id) {
+                Ok(transform) => transform,
+                Err(_) => continue,
+            },
+        };
+        transform.position.x += velocity.0.x * delta;
+        transform.position.y += velocity.0.y * delta;
+    }
+    for (id, angular_velocity) in (&angular_velocities).iter().with_id() {
+        let transform = match (&mut locals).get(id) {
+            Ok(local) => &mut local.0,
+            Err(_) => match (&mut transforms).get(id) {
+                Ok(transform) => transform,
+                Err(_) => continue,
+            },
+        };
+        transform.rotate(angular_velocity.0 * delta);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shipyard::{EntityId, World};
+    use ultraviolet::{Vec2, Vec3};
+
+    use super::*;
+
+
+    fn world(delta: f32) -> World {
+        let mut time = Time::new();
+        time.delta = delta;
+        let world = World::new();
+        world.add_unique(time);
+        world
+    }
+
+    fn position(world: &World, entity: EntityId) -> Vec2 {
+        world.run(|transforms: View<Transform>| {
+            let transform = (&transforms).get(entity).unwrap();
+            Vec2::new(transform.position.x, transform.position.y)
+        })
+    }
+
+    #[test]
+    fn velocity_is_updated_before_position() {
+        let mut world = world(0.5);
+        let entity = world.add_entity((
+            Transform::new(Vec3::zero(), Vec2::one()),
+            Velocity(Vec2::new(1.0, 0.0)),
+            Acceleration(Vec2::new(0.0, 2.0)),
+            AngularVelocity(1.0),
+        ));
+        world.run(integrate);
+        assert!((position(&world, entity) - Vec2::new(0.5, 0.5)).mag() < 1e-4);
+        world.run(|transforms: View<Transform>| {
+            assert!(((&transforms).get(entity).unwrap().angle() - 0.5).abs() < 1e-4);
+        });
+    }
+
+    #[test]
+    fn damping_decays_exponentially() {
+        let mut world = world(1.0);
+        let entity = world.add_entity((
+            Transform::default(),
+            Velocity(Vec2::new(1.0, 0.0)),
+            AngularVelocity(1.0),
+            Damping {
+                linear:  2.0_f32.ln(),
+                angular: 2.0_f32.ln(),
+            },
+        ));
+        world.run(integrate);
+        world.run(
+            |velocities: View<Velocity>, angular_velocities: View<AngularVelocity>| {
+                assert!(((&velocities).get(entity).unwrap().0.x - 0.5).abs() < 1e-4);
+                assert!(((&angular_velocities).get(entity).unwrap().0 - 0.5).abs() < 1e-4);
+            },
+        );
+    }
+
+    #[test]
+    fn children_move_their_local_transform() {
+        let mut world = world(1.0);
+        let entity = world.add_entity((
+            Transform::new(Vec3::new(5.0, 5.0, 0.0), Vec2::one()),
+            LocalTransform(Transform::new(Vec3::zero(), Vec2::one())),
+            Velocity(Vec2::new(1.0, 2.0)),
+        ));
+        world.run(integrate);
+        assert!((position(&world, entity) - Vec2::new(5.0, 5.0)).mag() < 1e-4);
+        world.run(|locals: View<LocalTransform>| {
+            let local = (&locals).get(entity).unwrap();
+            assert!((local.0.position.x - 1.0).abs() < 1e-4);
+            assert!((local.0.position.y - 2.0).abs() < 1e-4);
+        });
+    }
+
+    #[test]
+    fn paused_time_does_not_move() {
+        let mut world = world(0.0);
+        let entity = world.add_entity((
+            Transform::new(Vec3::new(1.0, 1.0, 0.0), Vec2::one()),
+            Velocity(Vec2::new(1.0, 0.0)),
+            Acceleration(Vec2::new(1.0, 0.0)),
+        ));
+        world.run(integrate);
+        assert!((position(&world, entity) - Vec2::one()).mag() < 1e-4);
+        world.run(|velocities: View<Velocity>| {
+            assert!(((&velocities).get(entity).unwrap().0.x - 1.0).abs() < 1e-4);
+        });
+    }
+}
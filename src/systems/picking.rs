@@ -0,0 +1,322 @@
+use std::{cmp::Reverse, collections::HashMap};
+
+use image::RgbaImage;
+use itertools::Itertools;
+use shipyard::{EntityId, Get, IntoIter, IntoWithId, UniqueView, UniqueViewMut, View};
+use ultraviolet::{Vec2, Vec3};
+use winit::event::MouseButton;
+
+use crate::{
+    components::{Camera, GlobalTransform, RenderLayers, Sprite, SpriteData, Transform},
+    graphics::{
+        blend::BlendMode, graph::Attachment, pipelines::SpritePipeline, renderer::Renderer,
+    },
+    resources::get_image,
+};
+
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PickEvent {
+    HoverStart(EntityId),
+    HoverEnd(EntityId),
+    Click(EntityId, MouseButton),
+}
+
+// world space origin and direction of the ray through a pixel, none outside the camera viewport
+pub fn camera_ray(camera: &Camera, cursor: Vec2, width: u32, height: u32) -> Option<(Vec3, Vec3)> {
+    let (x, y, w, h) = camera.viewport.rect(width, height);
+    let (x, y, w, h) = (x as f32, y as f32, w as f32, h as f32);
+    if cursor.x < x || cursor.y < y || cursor.x >= x + w || cursor.y >= y + h {
+        return None;
+    }
+    let ndc = Vec2::new(
+        (cursor.x - x) / w * 2.0 - 1.0,
+        1.0 - (cursor.y - y) / h * 2.0,
+    );
+    // the projection can't be inverted with znear at 0, the ray is built from its scale instead,
+    // with the aspect of the viewport since camera.aspect is only updated when rendering
+    let scale = (camera.fovy.to_radians() / 2.0).tan();
+    let direction = Vec3::new(
+        ndc.x * scale * camera.viewport.aspect(width, height),
+        ndc.y * scale,
+        -1.0,
+    );
+    let view = camera.view().inversed();
+    Some((
+        view.transform_point3(Vec3::zero()),
+        view.transform_vec3(direction).normalized(),
+    ))
+}
+
+// distance along the ray and texture coordinate where it crosses the sprite quad
+pub fn hit_sprite(
+    transform: &Transform, data: &SpriteData, origin: Vec3, direction: Vec3,
+) -> Option<(f32, Vec2)> {
+    if direction.z.abs() < f32::EPSILON || transform.size.x <= 0.0 || transform.size.y <= 0.0 {
+        return None;
+    }
+    let distance = (transform.position.z - origin.z) / direction.z;
+    if distance < 0.0 {
+        return None;
+    }
+    let point = origin + direction * distance;
+
+    // inverse of the quad placement in sprite/simple.vert
+    let (sin, cos) = (-transform.angle()).sin_cos();
+    let (x, y) = (
+        point.x - transform.position.x,
+        point.y - transform.position.y,
+    );
+    let corner = Vec2::new(
+        (x * cos - y * sin) / (2.0 * transform.size.x),
+        (x * sin + y * cos) / (2.0 * transform.size.y),
+    ) + data.pivot;
+    if corner.x < 0.0 || corner.y < 0.0 || corner.x > 1.0 || corner.y > 1.0 {
+        return None;
+    }
+    let mut uv = Vec2::new(corner.x, 1.0 - corner.y);
+    if data.flip_x() {
+        uv.x = 1.0 - uv.x;
+    }
+    if data.flip_y() {
+        uv.y = 1.0 - uv.y;
+    }
+    Some((distance, data.texture_position + uv * data.texture_size))
+}
+
+// the sprite under the cursor and the hover and click events since the last frame
+pub struct Picking {
+    // in physical pixels, none when the cursor left the window
+    pub cursor:          Option<Vec2>,
+    // pixels with a lower alpha are not picked, none picks the whole quad
+    pub alpha_threshold: Option<f32>,
+    pub hovered:         Option<EntityId>,
+    pub events:          Vec<PickEvent>,
+
+    clicks: Vec<MouseButton>,
+    images: HashMap<u64, Option<RgbaImage>>,
+}
+impl Picking {
+    pub fn new() -> Self {
+        Self {
+            cursor:          None,
+            alpha_threshold: Some(0.5),
+            hovered:         None,
+            events:          Vec::new(),
+            clicks:          Vec::new(),
+            images:          HashMap::new(),
+        }
+    }
+
+    pub fn click(&mut self, button: MouseButton) { self.clicks.push(button); }
+
+    fn alpha(&mut self, renderer: &Renderer, texture: u64, position: Vec2) -> f32 {
+        let image = self.images.entry(texture).or_insert_with(|| {
+            renderer
+                .get_texture_path(texture)
+                .and_then(|path| get_image(path).ok())
+                .map(|image| image.to_rgba8())
+        });
+        match image {
+            Some(image) => {
+                let (width, height) = image.dimensions();
+                let x = ((position.x * width as f32) as u32).min(width - 1);
+                let y = ((position.y * height as f32) as u32).min(height - 1);
+                image.get_pixel(x, y)[3] as f32 / 255.0
+            }
+            None => 1.0,
+        }
+    }
+
+    // the nearest sprite under a pixel, cameras drawn last are tested first and sprites at the
+    // same distance are won by the one drawn last
+    pub fn pick(
+        &mut self, renderer: &Renderer, cameras: &View<Camera>,
+        sprites: (&View<GlobalTransform>, &View<Sprite>, &View<RenderLayers>), cursor: Vec2,
+    ) -> Option<EntityId> {
+        let (transforms, sprites, layers) = sprites;
+        let mut pipeline = renderer.get_pipeline_mut::<SpritePipeline>();
+        let pipeline = pipeline.downcast_mut::<SpritePipeline>();
+        for camera in cameras
+            .iter()
+            .filter(|camera| !matches!(camera.output, Attachment::Texture(_)))
+            .sorted_by_key(|camera| Reverse(camera.order))
+        {
+            let (origin, direction) =
+                match camera_ray(camera, cursor, renderer.width, renderer.height) {
+                    Some(ray) => ray,
+                    None => continue,
+                };
+            // distance, draw order and entity
+            let mut nearest: Option<(f32, ((BlendMode, usize, RenderLayers), usize), EntityId)> =
+                None;
+            for (index, (id, (transform, sprite))) in
+                (transforms, sprites).iter().with_id().enumerate()
+            {
+                let sprite_layers = layers
+                    .get(id)
+                    .map_or(RenderLayers::DEFAULT, |layers| *layers);
+                if !sprite_layers.intersects(camera.layers) {
+                    continue;
+                }
+                let (distance, position) =
+                    match hit_sprite(&transform.0, sprite.data(), origin, direction) {
+                        Some(hit) => hit,
+                        None => continue,
+                    };
+                let order = (pipeline.draw_order(sprite, sprite_layers), index);
+                if nearest.map_or(false, |(nearest, nearest_order, _)| {
+                    nearest < distance || (nearest == distance && nearest_order > order)
+                }) {
+                    continue;
+                }
+                if let Some(threshold) = self.alpha_threshold {
+                    let data = sprite.data();
+                    let alpha =
+                        self.alpha(renderer, sprite.texture, position) * data.tint.w * data.opacity;
+                    if alpha < threshold {
+                        continue;
+                    }
+                }
+                nearest = Some((distance, order, id));
+            }
+            if let Some((.., id)) = nearest {
+                return Some(id);
+            }
+        }
+        None
+    }
+
+    pub fn update(
+        &mut self, renderer: &Renderer, cameras: &View<Camera>,
+        sprites: (&View<GlobalTransform>, &View<Sprite>, &View<RenderLayers>),
+    ) {
+        self.events.clear();
+        let hovered = match self.cursor {
+            Some(cursor) => self.pick(renderer, cameras, sprites, cursor),
+            None => None,
+        };
+        if hovered != self.hovered {
+            if let Some(previous) = self.hovered {
+                self.events.push(PickEvent::HoverEnd(previous));
+            }
+            if let Some(entity) = hovered {
+                self.events.push(PickEvent::HoverStart(entity));
+            }
+            self.hovered = hovered;
+        }
+        for button in self.clicks.drain(..) {
+            if let Some(entity) = hovered {
+                self.events.push(PickEvent::Click(entity, button));
+            }
+        }
+    }
+}
+impl Default for Picking {
+    fn default() -> Self { Self::new() }
+}
+
+pub fn pick(
+    mut picking: UniqueViewMut<Picking>, renderer: UniqueView<Renderer>, cameras: View<Camera>,
+    transforms: View<GlobalTransform>, sprites: View<Sprite>, layers: View<RenderLayers>,
+) {
+    picking.update(&renderer, &cameras, (&transforms, &sprites, &layers));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    fn approx(a: Vec2, b: Vec2) -> bool { (a - b).mag() < 1e-4 }
+
+    // straight down onto the sprite plane
+    fn hit(transform: &Transform, data: &SpriteData, x: f32, y: f32) -> Option<Vec2> {
+        hit_sprite(
+            transform,
+            data,
+            Vec3::new(x, y, 10.0),
+            Vec3::new(0.0, 0.0, -1.0),
+        )
+        .map(|(_, position)| position)
+    }
+
+    #[test]
+    fn hit_centered() {
+        let transform = Transform::new(Vec3::zero(), Vec2::one());
+        let data = SpriteData::default();
+        let (distance, position) = hit_sprite(
+            &transform,
+            &data,
+            Vec3::new(0.5, 0.5, 10.0),
+            Vec3::new(0.0, 0.0, -1.0),
+        )
+        .unwrap();
+        assert!((distance - 10.0).abs() < 1e-4);
+        assert!(approx(position, Vec2::new(0.75, 0.25)));
+        assert!(hit(&transform, &data, 1.5, 0.0).is_none());
+        assert!(hit(&transform, &data, 0.0, -1.5).is_none());
+    }
+
+    #[test]
+    fn hit_texture_rect() {
+        let transform = Transform::new(Vec3::zero(), Vec2::one());
+        let data = SpriteData::new(Vec2::new(0.5, 0.0), Vec2::new(0.25, 0.5));
+        let position = hit(&transform, &data, 0.5, 0.5).unwrap();
+        assert!(approx(position, Vec2::new(0.6875, 0.125)));
+    }
+
+    #[test]
+    fn hit_pivot() {
+        let transform = Transform::new(Vec3::zero(), Vec2::one());
+        let data = SpriteData::default().with_pivot(SpriteData::PIVOT_BOTTOM);
+        let position = hit(&transform, &data, 0.0, 1.5).unwrap();
+        assert!(approx(position, Vec2::new(0.5, 0.25)));
+        assert!(hit(&transform, &data, 0.0, -0.5).is_none());
+    }
+
+    #[test]
+    fn hit_rotated() {
+        let transform = Transform::new(Vec3::zero(), Vec2::new(2.0, 1.0)).with_degrees(90.0);
+        let data = SpriteData::default();
+        let position = hit(&transform, &data, 0.0, 1.5).unwrap();
+        assert!(approx(position, Vec2::new(0.875, 0.5)));
+        assert!(hit(&transform, &data, 1.5, 0.0).is_none());
+
+        // rotation is around the pivot
+        let data = data.with_pivot(SpriteData::PIVOT_BOTTOM);
+        assert!(hit(&transform, &data, -1.0, 0.0).is_some());
+        assert!(hit(&transform, &data, 1.0, 0.0).is_none());
+    }
+
+    #[test]
+    fn hit_flipped() {
+        let transform = Transform::new(Vec3::zero(), Vec2::one());
+        let data = SpriteData::default().with_flip(true, false);
+        let position = hit(&transform, &data, 0.5, 0.5).unwrap();
+        assert!(approx(position, Vec2::new(0.25, 0.25)));
+        let data = SpriteData::default().with_flip(false, true);
+        let position = hit(&transform, &data, 0.5, 0.5).unwrap();
+        assert!(approx(position, Vec2::new(0.75, 0.75)));
+    }
+
+    #[test]
+    fn miss_parallel_and_behind() {
+        let transform = Transform::new(Vec3::zero(), Vec2::one());
+        let data = SpriteData::default();
+        assert!(hit_sprite(
+            &transform,
+            &data,
+            Vec3::new(0.0, 0.0, 10.0),
+            Vec3::new(1.0, 0.0, 0.0)
+        )
+        .is_none());
+        assert!(hit_sprite(
+            &transform,
+            &data,
+            Vec3::new(0.0, 0.0, 10.0),
+            Vec3::new(0.0, 0.0, 1.0)
+        )
+        .is_none());
+    }
+}
@@ -1,46 +1,67 @@
-use shipyard::{UniqueView, UniqueViewMut, View};
-use wgpu::{
-    CommandEncoderDescriptor, LoadOp, Operations, RenderPassColorAttachmentDescriptor,
-    RenderPassDescriptor,
-};
+use std::time::Instant;
+
+use itertools::Itertools;
+use shipyard::{IntoIter, IntoWithId, UniqueView, UniqueViewMut, View, ViewMut};
 
 use crate::{
-    components::{Camera, Sprite, Transform},
-    graphics::{pipelines::*, renderer::Renderer},
+    components::{Camera, GlobalTransform, RenderLayers, Sprite},
+    graphics::{errors::ErrorContext, graph::RenderGraph, pipelines::*, renderer::Renderer},
 };
 
 
 pub fn render(
-    camera: UniqueView<Camera>, renderer: UniqueViewMut<Renderer>, transforms: View<Transform>,
-    sprites: View<Sprite>,
+    mut cameras: ViewMut<Camera>, renderer: UniqueViewMut<Renderer>,
+    mut graph: UniqueViewMut<RenderGraph>, post: UniqueView<PostProcess>,
+    transforms: View<GlobalTransform>, sprites: View<Sprite>, layers: View<RenderLayers>,
 ) {
     if renderer.frame.is_none() {
         return;
     }
-    let view = &renderer.frame.as_ref().unwrap().output.view;
 
-    let mut pipeline = renderer.get_pipeline_mut::<SpritePipeline>();
-    let pipeline = pipeline.downcast_mut::<SpritePipeline>();
+    for camera in (&mut cameras).iter() {
+        camera.aspect = camera.viewport.aspect(renderer.width, renderer.height);
+    }
+    let cameras = (&cameras)
+        .iter()
+        .with_id()
+        .sorted_by_key(|(_, camera)| camera.order)
+        .collect::<Vec<_>>();
+
+    {
+        let mut pipeline = renderer.get_pipeline_mut::<PostProcessPipeline>();
+        let pipeline = pipeline.downcast_mut::<PostProcessPipeline>();
+        let context = ErrorContext::new("render")
+            .pipeline(std::any::type_name::<PostProcessPipeline>())
+            .label("prepare");
+        let start = Instant::now();
+        renderer.error_scope(context, || {
+            pipeline.configure(&renderer, &mut graph, &post);
+            graph.update(&renderer);
+            pipeline.prepare(&renderer, &graph);
+        });
+        renderer.stats.add_prepare_time(start.elapsed());
+    }
 
-    pipeline.prepare(&renderer, (&transforms, &sprites), &camera);
+    // sprite passes depend on where post processing routes the scene
+    {
+        let mut pipeline = renderer.get_pipeline_mut::<SpritePipeline>();
+        pipeline
+            .downcast_mut::<SpritePipeline>()
+            .configure(&mut graph, &cameras);
+    }
 
-    let mut encoder = renderer
-        .device
-        .create_command_encoder(&CommandEncoderDescriptor { label: None });
     {
-        let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
-            color_attachments:        &[RenderPassColorAttachmentDescriptor {
-                attachment:     &view,
-                resolve_target: None,
-                ops:            Operations {
-                    load:  LoadOp::Load,
-                    store: true,
-                },
-            }],
-            depth_stencil_attachment: None,
-            label:                    None,
+        let mut pipeline = renderer.get_pipeline_mut::<SpritePipeline>();
+        let pipeline = pipeline.downcast_mut::<SpritePipeline>();
+        let context = ErrorContext::new("render")
+            .pipeline(std::any::type_name::<SpritePipeline>())
+            .label("prepare");
+        let start = Instant::now();
+        renderer.error_scope(context, || {
+            pipeline.prepare(&renderer, (&transforms, &sprites), &layers, &cameras);
         });
-        pipeline.draw(&renderer, &mut pass);
+        renderer.stats.add_prepare_time(start.elapsed());
     }
-    renderer.queue.submit(Some(encoder.finish()));
+
+    graph.execute(&renderer);
 }
@@ -1,35 +1,107 @@
-use rand::random;
-use shipyard::{system, UniqueViewMut, Workload, World};
+use std::path::Path;
+
+use rand::Rng as _;
+use serde::{Deserialize, Serialize};
+use shipyard::{system, AllStoragesViewMut, EntityId, UniqueView, UniqueViewMut, Workload, World};
 use ultraviolet::{Vec2, Vec4};
-use wgpu::{Device, Queue, Surface};
-use winit::event::WindowEvent;
+use wgpu::{Device, Queue, Surface, TextureFormat};
+use winit::event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
 
 use crate::{
-    components::{Camera, Sprite, SpriteData, Transform},
-    graphics::renderer::Renderer,
+    components::{Camera, Rng, Sprite, SpriteData, Time, Transform},
+    config::Config,
+    graphics::{
+        blend::BlendMode, capture::RecordConfig, errors::ErrorLog, graph::RenderGraph,
+        pipelines::PostProcess, renderer::Renderer,
+    },
+    resources::get_texture_config,
     systems::*,
 };
 
 
+const ERROR_REPORT: &str = "errors.json";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scene {
+    // a random seed is picked when none is set
+    pub seed:     Option<u64>,
+    // laid out in a square grid
+    pub entities: u32,
+    pub textures: Vec<String>,
+}
+impl Default for Scene {
+    fn default() -> Self {
+        Self {
+            seed:     None,
+            entities: 200 * 200,
+            textures: vec![
+                "monochrome_transparent_packed.png".to_owned(),
+                "colored_transparent_packed.png".to_owned(),
+            ],
+        }
+    }
+}
+
 pub struct Universe {
-    pub world: World,
+    pub world:    World,
+    // the scene the universe was built from with its seed resolved
+    pub scene:    Scene,
+    pub record:   RecordConfig,
+    // fixed simulation step, recording overrides it with its own
+    pub timestep: Option<f32>,
 }
 
 impl Universe {
-    pub fn new(device: Device, queue: Queue, surface: Surface) -> Self {
+    pub fn new(
+        device: Device, queue: Queue, surface: Option<Surface>, format: TextureFormat,
+        config: &Config,
+    ) -> Self {
+        let scene = Scene {
+            seed: Some(config.scene.seed.unwrap_or_else(rand::random)),
+            ..config.scene.clone()
+        };
+        let seed = scene.seed.unwrap();
+        println!("scene seed: {}", seed);
         let mut world = World::new();
 
-        world.add_unique(Camera::new(1.0));
+        world.add_entity((Camera::new(1.0),));
 
-        let renderer = Renderer::new(device, queue, surface);
+        let trace = config.trace_path();
+        let report = trace.map_or_else(
+            || Path::new(ERROR_REPORT).to_path_buf(),
+            |trace| trace.join(ERROR_REPORT),
+        );
+        let errors = ErrorLog::new(trace, Some(&report)).with_seed(seed);
+        let mut renderer = Renderer::new(device, queue, surface, format, config.srgb, errors);
+        renderer.set_sample_count(config.sample_count);
+        renderer.set_present_mode(config.present_mode);
+        renderer.screenshot_path = config.screenshots.clone();
 
-        let textures = [
-            "monochrome_transparent_packed.png",
-            "colored_transparent_packed.png",
-        ];
-        let elements = 200;
-        for x in 0..elements {
-            for y in 0..elements {
+        let mut rng = Rng::new(seed);
+        let elements = (scene.entities as f32).sqrt().ceil() as u32;
+        let recolor = scene
+            .textures
+            .iter()
+            .map(|texture| get_texture_config(texture).map_or(false, |config| config.recolor))
+            .collect::<Vec<_>>();
+        if !scene.textures.is_empty() {
+            for i in 0..scene.entities {
+                let (x, y) = (i / elements, i % elements);
+                let index = rng.gen_range(0..scene.textures.len());
+                let texture = &scene.textures[index];
+                let data = SpriteData::new(
+                    Vec2::new(
+                        (rng.gen::<f32>() * 48.0).round() / 48.0,
+                        (rng.gen::<f32>() * 22.0).round() / 22.0,
+                    ),
+                    Vec2::new(1.0 / 48.0, 1.0 / 22.0),
+                );
+                let data = if recolor[index] {
+                    data.with_tint(Vec4::new(rng.gen(), rng.gen(), rng.gen(), 1.0))
+                } else {
+                    data
+                };
                 world.add_entity((
                     Transform {
                         position: Vec4::new(
@@ -42,48 +114,122 @@ impl Universe {
                         size:     Vec4::new(5.0 / elements as f32, 5.0 / elements as f32, 0.5, 1.0),
                     },
                     Sprite {
-                        texture: *renderer
-                            .load_texture(textures[rand::random::<usize>() % 2])
-                            .key(),
-                        data:    SpriteData {
-                            texture_position: Vec2::new(
-                                (random::<f32>() * 48.0).round() / 48.0,
-                                (random::<f32>() * 22.0).round() / 22.0,
-                            ),
-                            texture_size:     Vec2::new(1.0 / 48.0, 1.0 / 22.0),
-                        },
+                        texture: *renderer.load_texture(texture).key(),
+                        data,
+                        sampler: None,
+                        blend: BlendMode::Alpha,
                     },
                 ));
             }
         }
 
         world.add_unique(renderer);
+        world.add_unique(rng);
+        world.add_unique(Time::new());
+        world.add_unique(Collisions::default());
+        world.add_unique(Picking::new());
+
+        world.add_unique(RenderGraph::new());
+        world.add_unique(PostProcess::default());
 
         Workload::builder("main")
+            .with_system(system!(integrate))
+            .with_system(system!(propagate_transforms))
+            .with_system(system!(collide))
+            .with_system(system!(pick))
             .with_system(system!(render))
             .add_to_world(&world)
             .unwrap();
 
-        Self { world }
+        let mut universe = Self {
+            world,
+            scene,
+            record: config.record.clone(),
+            timestep: config.timestep,
+        };
+        if universe.record.enabled {
+            universe.start_recording();
+        }
+        universe
+    }
+
+    pub fn attach(&mut self, child: EntityId, parent: EntityId) -> bool {
+        self.world
+            .run(|all_storages: AllStoragesViewMut| attach(&all_storages, child, parent))
+    }
+
+    pub fn detach(&mut self, child: EntityId) {
+        self.world
+            .run(|all_storages: AllStoragesViewMut| detach(&all_storages, child));
+    }
+
+    // despawns the entity and its children
+    pub fn despawn(&mut self, entity: EntityId) {
+        self.world
+            .run(|mut all_storages: AllStoragesViewMut| despawn(&mut all_storages, entity));
+    }
+
+    pub fn save_scene<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let scene = ron::ser::to_string_pretty(&self.scene, ron::ser::PrettyConfig::default())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        std::fs::write(path, scene)
     }
 
     pub fn render(&mut self) {
-        self.world.run(|mut renderer: UniqueViewMut<Renderer>| {
-            renderer.swap().unwrap();
-        });
+        let timestep = self.timestep;
+        self.world.run(
+            |mut renderer: UniqueViewMut<Renderer>, mut time: UniqueViewMut<Time>| {
+                renderer.swap();
+                time.timestep = renderer
+                    .recorder
+                    .as_ref()
+                    .map(|recorder| recorder.timestep())
+                    .or(timestep);
+                time.advance();
+            },
+        );
         self.world.run_workload("main");
         self.world.run(|mut renderer: UniqueViewMut<Renderer>| {
             renderer.present();
+            if renderer
+                .recorder
+                .as_ref()
+                .map_or(false, |recorder| recorder.is_finished())
+            {
+                renderer.stop_recording();
+            }
+        });
+    }
+
+    pub fn start_recording(&mut self) {
+        let record = &self.record;
+        self.world.run(|mut renderer: UniqueViewMut<Renderer>| {
+            renderer.start_recording(record);
         });
     }
 
+    // waits for the captured frames to be written
+    pub fn stop_recording(&mut self) {
+        self.world.run(|mut renderer: UniqueViewMut<Renderer>| {
+            renderer.stop_recording();
+        });
+    }
+
+    pub fn toggle_recording(&mut self) {
+        let recording = self
+            .world
+            .run(|renderer: UniqueView<Renderer>| renderer.recorder.is_some());
+        if recording {
+            self.stop_recording();
+        } else {
+            self.start_recording();
+        }
+    }
+
     pub fn resize(&mut self, width: u32, height: u32) {
-        self.world.run(
-            |mut renderer: UniqueViewMut<Renderer>, mut camera: UniqueViewMut<Camera>| {
-                camera.aspect = width as f32 / height as f32;
-                renderer.create_swap_chain(width, height);
-            },
-        );
+        self.world.run(|mut renderer: UniqueViewMut<Renderer>| {
+            renderer.create_swap_chain(width, height);
+        });
     }
 
     pub fn event(&mut self, event: WindowEvent) {
@@ -94,6 +240,47 @@ impl Universe {
             WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
                 self.resize(new_inner_size.width, new_inner_size.height);
             }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::F12),
+                        ..
+                    },
+                ..
+            } => {
+                self.world.run(|renderer: UniqueView<Renderer>| {
+                    renderer.request_screenshot();
+                });
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::F11),
+                        ..
+                    },
+                ..
+            } => self.toggle_recording(),
+            WindowEvent::CursorMoved { position, .. } => {
+                self.world.run(|mut picking: UniqueViewMut<Picking>| {
+                    picking.cursor = Some(Vec2::new(position.x as f32, position.y as f32));
+                });
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.world.run(|mut picking: UniqueViewMut<Picking>| {
+                    picking.cursor = None;
+                });
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button,
+                ..
+            } => {
+                self.world.run(|mut picking: UniqueViewMut<Picking>| {
+                    picking.click(button);
+                });
+            }
             _ => (),
         };
     }
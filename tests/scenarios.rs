@@ -0,0 +1,22 @@
+use wgpu_bug_validation_errors::{config::Config, scenario::*};
+
+
+// every scenario in data/scenarios renders without validation errors, needs a gpu adapter
+#[test]
+#[ignore = "needs a gpu adapter, run with --ignored"]
+fn scenarios() {
+    let config = Config::default();
+    let paths = get_scenario_paths();
+    assert!(!paths.is_empty(), "no scenarios found");
+    for path in paths {
+        let scenario = get_scenario(&path).unwrap();
+        let errors = run_scenario(&config, &scenario, None)
+            .unwrap_or_else(|e| panic!("scenario {} did not run: {}", scenario.name, e));
+        assert!(
+            errors.is_empty(),
+            "scenario {} failed with {:?}",
+            scenario.name,
+            errors
+        );
+    }
+}